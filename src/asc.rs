@@ -0,0 +1,496 @@
+//! MPEG-4 AudioSpecificConfig, as defined in ISO/IEC 14496-3 1.6.2.1.
+
+use std::cmp;
+
+static SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+// usacSamplingFrequencyIndex, ISO/IEC 23003-3 Table 72. Reserved indices are 0.
+static USAC_SAMPLING_FREQUENCIES: [u32; 31] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350, 0, 0,
+    57600, 51200, 40000, 38400, 34150, 28800, 25600, 20000, 19200, 17075, 14400, 12800, 9600, 0, 0, 0,
+];
+
+const AOT_SBR: u8 = 5;
+const AOT_ER_BSAC: u8 = 22;
+const AOT_PS: u8 = 29;
+const AOT_ER_AAC_ELD: u8 = 39;
+const AOT_USAC: u8 = 42;
+
+const SYNC_EXTENSION_SBR: u32 = 0x2b7;
+const SYNC_EXTENSION_PS: u32 = 0x548;
+
+/// An owned AudioSpecificConfig together with a parsed view of its most
+/// commonly needed fields. The raw bytes are suitable for MP4 `esds` boxes,
+/// SDP `config=` parameters and `dec::Decoder::config_raw`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioSpecificConfig {
+    bytes: Vec<u8>,
+    audio_object_type: u8,
+    sampling_frequency_index: u8,
+    sampling_frequency: u32,
+    channel_configuration: u8,
+    extension_audio_object_type: Option<u8>,
+    extension_sampling_frequency: Option<u32>,
+    sbr_present: bool,
+    ps_present: bool,
+    frame_length: Option<u32>,
+}
+
+impl AudioSpecificConfig {
+    /// Parses a binary AudioSpecificConfig. Returns `None` if the buffer is
+    /// truncated or signals a reserved sampling frequency index or USAC
+    /// frame length.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut r = BitReader::new(bytes);
+
+        let mut audio_object_type = read_audio_object_type(&mut r)?;
+        let (sampling_frequency_index, sampling_frequency) = read_sampling_frequency(&mut r)?;
+        let channel_configuration = r.read(4)? as u8;
+
+        let mut asc = AudioSpecificConfig {
+            bytes: bytes.to_vec(),
+            audio_object_type,
+            sampling_frequency_index,
+            sampling_frequency,
+            channel_configuration,
+            extension_audio_object_type: None,
+            extension_sampling_frequency: None,
+            sbr_present: false,
+            ps_present: false,
+            frame_length: None,
+        };
+
+        // explicit hierarchical signaling of SBR/PS
+        if audio_object_type == AOT_SBR || audio_object_type == AOT_PS {
+            asc.extension_audio_object_type = Some(AOT_SBR);
+            asc.sbr_present = true;
+            asc.ps_present = audio_object_type == AOT_PS;
+            asc.extension_sampling_frequency = Some(read_sampling_frequency(&mut r)?.1);
+
+            audio_object_type = read_audio_object_type(&mut r)?;
+            asc.audio_object_type = audio_object_type;
+
+            if audio_object_type == AOT_ER_BSAC {
+                // extensionChannelConfiguration
+                r.read(4)?;
+            }
+        }
+
+        match audio_object_type {
+            1 | 2 | 3 | 4 | 6 | 7 | 17 | 19 | 20 | 21 | 22 | 23 => {
+                let short_frame = r.read(1)? == 1;
+
+                asc.frame_length = Some(match (audio_object_type, short_frame) {
+                    (23, false) => 512,
+                    (23, true) => 480,
+                    (_, false) => 1024,
+                    (_, true) => 960,
+                });
+
+                if read_ga_specific_config_tail(&mut r, audio_object_type, channel_configuration).is_some() {
+                    read_sync_extension(&mut r, &mut asc);
+                }
+            }
+            AOT_ER_AAC_ELD => {
+                asc.frame_length = Some(if r.read(1)? == 1 { 480 } else { 512 });
+
+                // aacSectionDataResilienceFlag, aacScalefactorDataResilienceFlag,
+                // aacSpectralDataResilienceFlag
+                r.read(3)?;
+
+                asc.sbr_present = r.read(1)? == 1;
+            }
+            AOT_USAC => {
+                // usacSamplingFrequencyIndex, the output sampling frequency
+                let index = r.read(5)?;
+
+                let output_frequency = if index == 0x1f {
+                    r.read(24)?
+                } else {
+                    match USAC_SAMPLING_FREQUENCIES.get(index as usize) {
+                        Some(&frequency) if frequency != 0 => frequency,
+                        _ => return None,
+                    }
+                };
+
+                // coreSbrFrameLengthIndex, ISO/IEC 23003-3 Table 70. The SBR
+                // ratio is given as output:core sampling frequency.
+                let (frame_length, sbr_ratio) = match r.read(3)? {
+                    0 => (768, None),
+                    1 => (1024, None),
+                    2 => (768, Some((8, 3))),
+                    3 => (1024, Some((2, 1))),
+                    4 => (1024, Some((4, 1))),
+                    _ => return None,
+                };
+
+                asc.frame_length = Some(frame_length);
+                asc.sampling_frequency = output_frequency;
+
+                if let Some((output, core)) = sbr_ratio {
+                    asc.sampling_frequency = (output_frequency as u64 * core / output) as u32;
+                    asc.extension_sampling_frequency = Some(output_frequency);
+                    asc.sbr_present = true;
+                }
+            }
+            _ => {}
+        }
+
+        Some(asc)
+    }
+
+    /// The raw AudioSpecificConfig bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Audio object type of the core codec, eg. 2 for AAC-LC. For
+    /// explicitly signaled HE-AAC this is the underlying core AOT.
+    pub fn audio_object_type(&self) -> u8 {
+        self.audio_object_type
+    }
+
+    /// samplingFrequencyIndex of the AudioSpecificConfig header, 0xf if the
+    /// frequency is given explicitly. For USAC with SBR the header signals
+    /// the output sampling frequency, so this index refers to
+    /// `extension_sampling_frequency` rather than `sampling_frequency`.
+    pub fn sampling_frequency_index(&self) -> u8 {
+        self.sampling_frequency_index
+    }
+
+    /// Sampling frequency of the core codec in Hz. With SBR the output
+    /// sampling frequency is `extension_sampling_frequency`, this includes
+    /// USAC, where the core rate is derived from the UsacConfig.
+    pub fn sampling_frequency(&self) -> u32 {
+        self.sampling_frequency
+    }
+
+    /// Channel configuration, 0 means the layout is defined by a program
+    /// config element.
+    pub fn channel_configuration(&self) -> u8 {
+        self.channel_configuration
+    }
+
+    /// Extension audio object type, `Some(5)` if SBR is signaled through
+    /// the extension mechanism.
+    pub fn extension_audio_object_type(&self) -> Option<u8> {
+        self.extension_audio_object_type
+    }
+
+    /// Output sampling frequency in Hz of the SBR extension, if signaled.
+    /// For USAC this is set whenever SBR is used, without an extension
+    /// audio object type.
+    pub fn extension_sampling_frequency(&self) -> Option<u32> {
+        self.extension_sampling_frequency
+    }
+
+    pub fn sbr_present(&self) -> bool {
+        self.sbr_present
+    }
+
+    pub fn ps_present(&self) -> bool {
+        self.ps_present
+    }
+
+    /// Number of samples per channel in one core codec frame, eg. 1024 or
+    /// 960 for AAC-LC, 512 or 480 for AAC-LD/ELD and 768 or 1024 for USAC.
+    /// `None` for audio object types whose specific config is not
+    /// understood.
+    pub fn frame_length(&self) -> Option<u32> {
+        self.frame_length
+    }
+}
+
+fn read_audio_object_type(r: &mut BitReader) -> Option<u8> {
+    let aot = r.read(5)? as u8;
+
+    if aot == 31 {
+        Some(32 + r.read(6)? as u8)
+    } else {
+        Some(aot)
+    }
+}
+
+fn read_sampling_frequency(r: &mut BitReader) -> Option<(u8, u32)> {
+    let index = r.read(4)? as u8;
+
+    if index == 0xf {
+        Some((index, r.read(24)?))
+    } else {
+        Some((index, *SAMPLING_FREQUENCIES.get(index as usize)?))
+    }
+}
+
+// Reads the remainder of GASpecificConfig after frameLengthFlag, and the
+// epConfig following it. Returns `None` when the rest of the config cannot
+// be located, ie. when a program config element or error protection config
+// is present.
+fn read_ga_specific_config_tail(r: &mut BitReader, audio_object_type: u8, channel_configuration: u8) -> Option<()> {
+    // dependsOnCoreCoder
+    if r.read(1)? == 1 {
+        // coreCoderDelay
+        r.read(14)?;
+    }
+
+    let extension_flag = r.read(1)? == 1;
+
+    if channel_configuration == 0 {
+        return None;
+    }
+
+    if audio_object_type == 6 || audio_object_type == 20 {
+        // layerNr
+        r.read(3)?;
+    }
+
+    if extension_flag {
+        match audio_object_type {
+            // numOfSubFrame, layer_length
+            22 => { r.read(16)?; }
+            // aacSectionDataResilienceFlag, aacScalefactorDataResilienceFlag,
+            // aacSpectralDataResilienceFlag
+            17 | 19 | 20 | 23 => { r.read(3)?; }
+            _ => {}
+        }
+
+        // extensionFlag3
+        r.read(1)?;
+    }
+
+    if let 17 | 19 | 20 | 21 | 22 | 23 = audio_object_type {
+        let ep_config = r.read(2)?;
+
+        if ep_config == 2 || ep_config == 3 {
+            return None;
+        }
+    }
+
+    Some(())
+}
+
+// Backward compatible (implicit) signaling of SBR and PS, appended after the
+// core config.
+fn read_sync_extension(r: &mut BitReader, asc: &mut AudioSpecificConfig) -> Option<()> {
+    if asc.extension_audio_object_type.is_some() || r.remaining() < 16 {
+        return None;
+    }
+
+    if r.read(11)? != SYNC_EXTENSION_SBR {
+        return None;
+    }
+
+    if read_audio_object_type(r)? != AOT_SBR || r.read(1)? == 0 {
+        return None;
+    }
+
+    asc.extension_audio_object_type = Some(AOT_SBR);
+    asc.sbr_present = true;
+    asc.extension_sampling_frequency = Some(read_sampling_frequency(r)?.1);
+
+    if r.remaining() >= 12 && r.read(11)? == SYNC_EXTENSION_PS {
+        asc.ps_present = r.read(1)? == 1;
+    }
+
+    Some(())
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, bits: usize) -> Option<u32> {
+        if bits > self.remaining() {
+            return None;
+        }
+
+        let mut value = 0u32;
+        let mut bits = bits;
+
+        while bits > 0 {
+            let byte = self.data[self.pos / 8] as u32;
+            let offset = self.pos % 8;
+            let take = cmp::min(bits, 8 - offset);
+            let chunk = (byte >> (8 - offset - take)) & ((1 << take) - 1);
+
+            value = (value << take) | chunk;
+            self.pos += take;
+            bits -= take;
+        }
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AudioSpecificConfig;
+
+    #[test]
+    fn aac_lc() {
+        let asc = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+
+        assert_eq!(asc.audio_object_type(), 2);
+        assert_eq!(asc.sampling_frequency_index(), 4);
+        assert_eq!(asc.sampling_frequency(), 44100);
+        assert_eq!(asc.channel_configuration(), 2);
+        assert_eq!(asc.extension_audio_object_type(), None);
+        assert_eq!(asc.extension_sampling_frequency(), None);
+        assert!(!asc.sbr_present());
+        assert!(!asc.ps_present());
+        assert_eq!(asc.frame_length(), Some(1024));
+        assert_eq!(asc.as_bytes(), &[0x12, 0x10]);
+    }
+
+    #[test]
+    fn explicit_he_aac() {
+        // AOT 5, 22050Hz, stereo, extension 44100Hz, AOT 2
+        let asc = AudioSpecificConfig::parse(&[0x2b, 0x92, 0x08, 0x00]).unwrap();
+
+        assert_eq!(asc.audio_object_type(), 2);
+        assert_eq!(asc.sampling_frequency(), 22050);
+        assert_eq!(asc.channel_configuration(), 2);
+        assert_eq!(asc.extension_audio_object_type(), Some(5));
+        assert_eq!(asc.extension_sampling_frequency(), Some(44100));
+        assert!(asc.sbr_present());
+        assert!(!asc.ps_present());
+        assert_eq!(asc.frame_length(), Some(1024));
+    }
+
+    #[test]
+    fn explicit_he_aac_v2() {
+        // AOT 29, 24000Hz, stereo, extension 48000Hz, AOT 2
+        let asc = AudioSpecificConfig::parse(&[0xeb, 0x11, 0x88, 0x00]).unwrap();
+
+        assert_eq!(asc.audio_object_type(), 2);
+        assert_eq!(asc.sampling_frequency(), 24000);
+        assert_eq!(asc.extension_audio_object_type(), Some(5));
+        assert_eq!(asc.extension_sampling_frequency(), Some(48000));
+        assert!(asc.sbr_present());
+        assert!(asc.ps_present());
+    }
+
+    #[test]
+    fn implicit_sbr() {
+        // AAC-LC 22050Hz stereo, sync extension 0x2b7 with SBR at 48000Hz
+        let asc = AudioSpecificConfig::parse(&[0x13, 0x90, 0x56, 0xe5, 0x98]).unwrap();
+
+        assert_eq!(asc.audio_object_type(), 2);
+        assert_eq!(asc.sampling_frequency(), 22050);
+        assert_eq!(asc.extension_audio_object_type(), Some(5));
+        assert_eq!(asc.extension_sampling_frequency(), Some(48000));
+        assert!(asc.sbr_present());
+        assert!(!asc.ps_present());
+    }
+
+    #[test]
+    fn implicit_ps() {
+        // AAC-LC 24000Hz mono, sync extension 0x2b7 with SBR at 48000Hz,
+        // followed by sync extension 0x548 with PS
+        let asc = AudioSpecificConfig::parse(&[0x13, 0x08, 0x56, 0xe5, 0x9d, 0x48, 0x80]).unwrap();
+
+        assert_eq!(asc.sampling_frequency(), 24000);
+        assert_eq!(asc.channel_configuration(), 1);
+        assert_eq!(asc.extension_sampling_frequency(), Some(48000));
+        assert!(asc.sbr_present());
+        assert!(asc.ps_present());
+    }
+
+    #[test]
+    fn eld_with_ld_sbr() {
+        // AOT 39 (escaped), 48000Hz, mono, 512 samples, ldSbrPresentFlag
+        let asc = AudioSpecificConfig::parse(&[0xf8, 0xe6, 0x21]).unwrap();
+
+        assert_eq!(asc.audio_object_type(), 39);
+        assert_eq!(asc.sampling_frequency(), 48000);
+        assert_eq!(asc.channel_configuration(), 1);
+        assert_eq!(asc.frame_length(), Some(512));
+        assert!(asc.sbr_present());
+    }
+
+    #[test]
+    fn escape_sampling_frequency() {
+        // AAC-LC, explicit 50000Hz, mono
+        let asc = AudioSpecificConfig::parse(&[0x17, 0x80, 0x61, 0xa8, 0x08]).unwrap();
+
+        assert_eq!(asc.sampling_frequency_index(), 0xf);
+        assert_eq!(asc.sampling_frequency(), 50000);
+        assert_eq!(asc.channel_configuration(), 1);
+    }
+
+    // AOT 42, 48000Hz, stereo, usacSamplingFrequencyIndex 3 (48000Hz),
+    // followed by coreSbrFrameLengthIndex in the top bits of the last byte
+    fn usac(core_sbr_frame_length_index: u8) -> Option<AudioSpecificConfig> {
+        AudioSpecificConfig::parse(&[0xf9, 0x46, 0x43, core_sbr_frame_length_index << 5])
+    }
+
+    #[test]
+    fn usac_core_sbr_frame_length() {
+        let expected = [
+            (768, 48000, None),
+            (1024, 48000, None),
+            (768, 18000, Some(48000)),
+            (1024, 24000, Some(48000)),
+            (1024, 12000, Some(48000)),
+        ];
+
+        for (index, &(frame_length, sampling_frequency, extension_sampling_frequency)) in expected.iter().enumerate() {
+            let asc = usac(index as u8).unwrap();
+
+            assert_eq!(asc.audio_object_type(), 42);
+            assert_eq!(asc.channel_configuration(), 2);
+            assert_eq!(asc.frame_length(), Some(frame_length), "index {}", index);
+            // the header index is that of the 48000Hz output
+            assert_eq!(asc.sampling_frequency_index(), 3, "index {}", index);
+            assert_eq!(asc.sampling_frequency(), sampling_frequency, "index {}", index);
+            assert_eq!(asc.extension_sampling_frequency(), extension_sampling_frequency, "index {}", index);
+            assert_eq!(asc.sbr_present(), extension_sampling_frequency.is_some(), "index {}", index);
+            assert_eq!(asc.extension_audio_object_type(), None);
+            assert!(!asc.ps_present());
+        }
+
+        // reserved
+        for index in 5..8 {
+            assert!(usac(index).is_none(), "index {}", index);
+        }
+    }
+
+    #[test]
+    fn usac_sampling_frequency() {
+        // usacSamplingFrequencyIndex 31, escaped 50000Hz, 2:1 SBR
+        let asc = AudioSpecificConfig::parse(&[0xf9, 0x46, 0x5f, 0x00, 0xc3, 0x50, 0x60]).unwrap();
+
+        assert_eq!(asc.sampling_frequency(), 25000);
+        assert_eq!(asc.extension_sampling_frequency(), Some(50000));
+
+        // reserved usacSamplingFrequencyIndex 13
+        assert!(AudioSpecificConfig::parse(&[0xf9, 0x46, 0x4d, 0x20]).is_none());
+        // truncated escaped frequency
+        assert!(AudioSpecificConfig::parse(&[0xf9, 0x46, 0x5f, 0x00]).is_none());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(AudioSpecificConfig::parse(&[]).is_none());
+        assert!(AudioSpecificConfig::parse(&[0x12]).is_none());
+        // truncated extension sampling frequency of explicit HE-AAC
+        assert!(AudioSpecificConfig::parse(&[0x2b, 0x92]).is_none());
+        // reserved sampling frequency index 13
+        assert!(AudioSpecificConfig::parse(&[0x16, 0x90]).is_none());
+    }
+}
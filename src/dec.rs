@@ -1,10 +1,11 @@
+use std::cmp;
 use std::fmt::{self, Display, Debug};
 use std::os::raw::{c_uint, c_int};
+use std::ptr::{self, NonNull};
+use std::slice;
 
 use fdk_aac_sys as sys;
 
-pub use sys::CStreamInfo as StreamInfo;
-
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct DecoderError(sys::AAC_DECODER_ERROR);
 
@@ -42,6 +43,14 @@ impl DecoderError {
     pub const TOO_SMALL_ANC_BUFFER: DecoderError = DecoderError(sys::AAC_DECODER_ERROR_AAC_DEC_TOO_SMALL_ANC_BUFFER);
     pub const TOO_MANY_ANC_ELEMENTS: DecoderError = DecoderError(sys::AAC_DECODER_ERROR_AAC_DEC_TOO_MANY_ANC_ELEMENTS);
 
+    // decode and ancillary data errors still produce a valid, possibly
+    // concealed, output frame
+    fn output_valid(self) -> bool {
+        matches!(self.0,
+            sys::AAC_DECODER_ERROR_aac_dec_decode_error_start..=sys::AAC_DECODER_ERROR_aac_dec_decode_error_end |
+            sys::AAC_DECODER_ERROR_aac_dec_anc_data_error_start..=sys::AAC_DECODER_ERROR_aac_dec_anc_data_error_end)
+    }
+
     pub fn message(&self) -> &'static str {
         match self.0 {
             sys::AAC_DECODER_ERROR_AAC_DEC_OK => "No error occurred. Output buffer is valid and error free.",
@@ -106,59 +115,257 @@ fn check(e: sys::AACENC_ERROR) -> Result<(), DecoderError> {
     }
 }
 
+/// Maximum number of transport layers supported by libfdk-aac.
+const MAX_LAYERS: u32 = 1;
+
+/// Validates decoder configuration before opening a libfdk-aac decoder.
+#[derive(Clone, Copy, Debug)]
+pub struct DecoderBuilder {
+    transport: Transport,
+    layers: u32,
+}
+
+impl DecoderBuilder {
+    pub fn new(transport: Transport) -> Self {
+        DecoderBuilder { transport, layers: 1 }
+    }
+
+    /// Number of transport layers to decode. libfdk-aac supports only a
+    /// single layer, which is the default.
+    pub fn layers(mut self, layers: u32) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Opens the decoder. Fails with `DecoderError::SET_PARAM_FAIL` for zero
+    /// layers, `DecoderError::UNSUPPORTED_MULTILAYER` for more layers than
+    /// supported and `DecoderError::OUT_OF_MEMORY` if libfdk-aac could not
+    /// allocate the decoder.
+    pub fn build(self) -> Result<Decoder, DecoderError> {
+        let handle = self.open()?;
+
+        Ok(Decoder {
+            handle,
+            builder: self,
+            params: Vec::new(),
+            ancillary: None,
+            reorder: None,
+            pending_flags: 0,
+            limiter: LimiterConfig::default(),
+        })
+    }
+
+    fn open(&self) -> Result<NonNull<sys::AAC_DECODER_INSTANCE>, DecoderError> {
+        if self.layers == 0 {
+            return Err(DecoderError::SET_PARAM_FAIL);
+        }
+
+        if self.layers > MAX_LAYERS {
+            return Err(DecoderError::UNSUPPORTED_MULTILAYER);
+        }
+
+        let handle = unsafe { sys::aacDecoder_Open(self.transport.to_sys(), self.layers) };
+        NonNull::new(handle).ok_or(DecoderError::OUT_OF_MEMORY)
+    }
+}
+
 #[derive(Debug)]
 pub struct Decoder {
-    handle: sys::HANDLE_AACDECODER,
+    handle: NonNull<sys::AAC_DECODER_INSTANCE>,
+    // configuration and parameters to reopen the decoder with on `restart`
+    builder: DecoderBuilder,
+    params: Vec<(sys::AACDEC_PARAM, i32)>,
+    ancillary: Option<Box<[u8]>>,
+    reorder: Option<ChannelReorder>,
+    // decode flags to pass with the next successfully decoded frame
+    pending_flags: u32,
+    limiter: LimiterConfig,
 }
 
 unsafe impl Send for Decoder {}
 unsafe impl Sync for Decoder {}
 
 impl Decoder {
-    pub fn new(transport: Transport) -> Self {
-        let handle = match transport {
-            Transport::Raw => {
-                unsafe { sys::aacDecoder_Open(sys::TRANSPORT_TYPE_TT_MP4_RAW, 1) }
-            }
-            Transport::Adts => {
-                unsafe { sys::aacDecoder_Open(sys::TRANSPORT_TYPE_TT_MP4_ADTS, 1) }
-            }
-        };
-
-        Decoder { handle }
+    /// Opens a single layer decoder, see `DecoderBuilder` for other
+    /// configurations.
+    pub fn new(transport: Transport) -> Result<Self, DecoderError> {
+        DecoderBuilder::new(transport).build()
     }
 
+    /// Configures the decoder out-of-band, eg. for `Transport::Raw` with an
+    /// AudioSpecificConfig from an MP4 `esds` box. This includes USAC
+    /// (xHE-AAC) configs, for which the AudioSpecificConfig carries a
+    /// UsacConfig.
+    ///
+    /// Fails with `DecoderError::NEED_TO_RESTART` if the decoder was already
+    /// configured and cannot switch to the new config, see `restart`.
     pub fn config_raw(&mut self, audio_specic_config: &[u8]) -> Result<(), DecoderError> {
         unsafe {
             let mut asc_ptr = audio_specic_config.as_ptr() as *mut u8;
             let asc_len = audio_specic_config.len() as c_uint;
-            check(sys::aacDecoder_ConfigRaw(self.handle, &mut asc_ptr as *mut _, &asc_len as *const _))
+            check(sys::aacDecoder_ConfigRaw(self.handle.as_ptr(), &mut asc_ptr as *mut _, &asc_len as *const _))
         }
     }
 
-    pub fn set_min_output_channels(&mut self, channels: usize) -> Result<(), DecoderError> {
-        unsafe {
-            check(sys::aacDecoder_SetParam(self.handle,
-                sys::AACDEC_PARAM_AAC_PCM_MIN_OUTPUT_CHANNELS,
-                channels as i32))
+    fn set_param(&mut self, param: sys::AACDEC_PARAM, value: i32) -> Result<(), DecoderError> {
+        self.apply_param(param, value)?;
+
+        self.params.retain(|&(p, _)| p != param);
+        self.params.push((param, value));
+        Ok(())
+    }
+
+    // sets a parameter without restoring it on `restart`
+    fn apply_param(&self, param: sys::AACDEC_PARAM, value: i32) -> Result<(), DecoderError> {
+        check(unsafe { sys::aacDecoder_SetParam(self.handle.as_ptr(), param, value) })
+    }
+
+    /// Reopens the decoder with the same transport and parameters, after
+    /// `config_raw` or `decode_frame` failed with
+    /// `DecoderError::NEED_TO_RESTART`. This happens when a stream changes
+    /// to a configuration the running decoder cannot switch to, eg. a USAC
+    /// config change. Buffered input is discarded, raw transports need to
+    /// be configured again with `config_raw`.
+    pub fn restart(&mut self) -> Result<(), DecoderError> {
+        let handle = self.builder.open()?;
+
+        unsafe { sys::aacDecoder_Close(self.handle.as_ptr()); }
+        self.handle = handle;
+        self.pending_flags = 0;
+
+        for &(param, value) in &self.params {
+            self.apply_param(param, value)?;
+        }
+
+        if let Some(buffer) = &mut self.ancillary {
+            unsafe {
+                check(sys::aacDecoder_AncDataInit(self.handle.as_ptr(),
+                    buffer.as_mut_ptr(),
+                    buffer.len() as c_int))?;
+            }
         }
+
+        Ok(())
+    }
+
+    pub fn set_min_output_channels(&mut self, channels: usize) -> Result<(), DecoderError> {
+        self.set_param(sys::AACDEC_PARAM_AAC_PCM_MIN_OUTPUT_CHANNELS, channels as i32)
     }
 
     pub fn set_max_output_channels(&mut self, channels: usize) -> Result<(), DecoderError> {
-        unsafe {
-            check(sys::aacDecoder_SetParam(self.handle,
-                sys::AACDEC_PARAM_AAC_PCM_MAX_OUTPUT_CHANNELS,
-                channels as i32))
+        self.set_param(sys::AACDEC_PARAM_AAC_PCM_MAX_OUTPUT_CHANNELS, channels as i32)
+    }
+
+    /// Configures how the decoded channels are mapped and downmixed to the
+    /// output. Fails with `DecoderError::SET_PARAM_FAIL` for an unsupported
+    /// channel count, leaving the downmix settings unchanged.
+    ///
+    /// Downmixing follows the downmix metadata carried in the stream, eg. to
+    /// decode any stream to at most stereo:
+    ///
+    /// ```
+    /// use fdk_aac::dec::{Decoder, DownmixConfig, OutputChannels, Transport};
+    ///
+    /// let mut decoder = Decoder::new(Transport::Adts).unwrap();
+    /// decoder.set_downmix(&DownmixConfig {
+    ///     max_output_channels: Some(OutputChannels::Count(2)),
+    ///     ..Default::default()
+    /// }).unwrap();
+    /// ```
+    pub fn set_downmix(&mut self, config: &DownmixConfig) -> Result<(), DecoderError> {
+        let min_output_channels = config.min_output_channels.map(OutputChannels::to_sys).transpose()?;
+        let max_output_channels = config.max_output_channels.map(OutputChannels::to_sys).transpose()?;
+
+        if let Some(mode) = config.dual_channel_output_mode {
+            self.set_param(sys::AACDEC_PARAM_AAC_PCM_DUAL_CHANNEL_OUTPUT_MODE, mode as i32)?;
+        }
+
+        if let Some(mapping) = config.channel_mapping {
+            self.set_param(sys::AACDEC_PARAM_AAC_PCM_OUTPUT_CHANNEL_MAPPING, mapping as i32)?;
+        }
+
+        if let Some(channels) = min_output_channels {
+            self.set_param(sys::AACDEC_PARAM_AAC_PCM_MIN_OUTPUT_CHANNELS, channels)?;
+        }
+
+        if let Some(channels) = max_output_channels {
+            self.set_param(sys::AACDEC_PARAM_AAC_PCM_MAX_OUTPUT_CHANNELS, channels)?;
         }
+
+        Ok(())
+    }
+
+    /// Configures dynamic range control and loudness normalization. Fails
+    /// with `DecoderError::SET_PARAM_FAIL` for an out of range factor or
+    /// level, leaving the DRC settings unchanged.
+    pub fn set_drc(&mut self, config: &DrcConfig) -> Result<(), DecoderError> {
+        config.validate()?;
+
+        let reference_level = config.reference_level.map(DrcReferenceLevel::to_sys).transpose()?;
+
+        if let Some(factor) = config.boost_factor {
+            self.set_param(sys::AACDEC_PARAM_AAC_DRC_BOOST_FACTOR, drc_factor(factor))?;
+        }
+
+        if let Some(factor) = config.attenuation_factor {
+            self.set_param(sys::AACDEC_PARAM_AAC_DRC_ATTENUATION_FACTOR, drc_factor(factor))?;
+        }
+
+        if let Some(level) = reference_level {
+            self.set_param(sys::AACDEC_PARAM_AAC_DRC_REFERENCE_LEVEL, level)?;
+        }
+
+        if let Some(heavy_compression) = config.heavy_compression {
+            self.set_param(sys::AACDEC_PARAM_AAC_DRC_HEAVY_COMPRESSION, heavy_compression as i32)?;
+        }
+
+        if let Some(mode) = config.default_presentation_mode {
+            self.set_param(sys::AACDEC_PARAM_AAC_DRC_DEFAULT_PRESENTATION_MODE, mode as i32)?;
+        }
+
+        if let Some(level) = config.enc_target_level {
+            self.set_param(sys::AACDEC_PARAM_AAC_DRC_ENC_TARGET_LEVEL, drc_level(level))?;
+        }
+
+        if let Some(effect) = config.effect {
+            self.set_param(sys::AACDEC_PARAM_AAC_UNIDRC_SET_EFFECT, effect as i32)?;
+        }
+
+        if let Some(album_mode) = config.album_mode {
+            self.set_param(sys::AACDEC_PARAM_AAC_UNIDRC_ALBUM_MODE, album_mode as i32)?;
+        }
+
+        Ok(())
+    }
+
+    /// Configures the PCM limiter which prevents clipping of the decoded
+    /// output. An enabled limiter delays the output, see
+    /// `StreamInfo::limiter_delay`. Fails with `DecoderError::SET_PARAM_FAIL`
+    /// for an out of range attack or release time, leaving the limiter
+    /// unchanged.
+    pub fn set_limiter(&mut self, config: &LimiterConfig) -> Result<(), DecoderError> {
+        config.validate()?;
+
+        self.set_param(sys::AACDEC_PARAM_AAC_PCM_LIMITER_ENABLE, config.mode as i32)?;
+        self.set_param(sys::AACDEC_PARAM_AAC_PCM_LIMITER_ATTACK_TIME, config.attack_time as i32)?;
+        self.set_param(sys::AACDEC_PARAM_AAC_PCM_LIMITER_RELEAS_TIME, config.release_time as i32)?;
+
+        self.limiter = *config;
+        Ok(())
+    }
+
+    /// Selects how `conceal_frame` and corrupt frames are concealed.
+    pub fn set_conceal_method(&mut self, method: ConcealMethod) -> Result<(), DecoderError> {
+        self.set_param(sys::AACDEC_PARAM_AAC_CONCEAL_METHOD, method as i32)
     }
 
     pub fn fill(&mut self, data: &[u8]) -> Result<usize, DecoderError> {
         unsafe {
-            let mut data_ptr = data.as_ptr() as *const u8 as *mut u8;
+            let mut data_ptr = data.as_ptr() as *mut u8;
             let data_len = data.len() as c_uint;
             let mut bytes_valid: c_uint = data_len;
 
-            check(sys::aacDecoder_Fill(self.handle,
+            check(sys::aacDecoder_Fill(self.handle.as_ptr(),
                 &mut data_ptr as *mut _,
                 &data_len as *const _,
                 &mut bytes_valid as *mut _))?;
@@ -167,34 +374,1229 @@ impl Decoder {
         }
     }
 
+    /// Decodes one frame from the input provided through `fill`. Fails with
+    /// `DecoderError::NOT_ENOUGH_BITS` until enough input is available, and
+    /// with `DecoderError::NEED_TO_RESTART` on a config change requiring a
+    /// `restart`.
     pub fn decode_frame(&mut self, pcm: &mut [i16]) -> Result<(), DecoderError> {
-        unsafe {
-            check(sys::aacDecoder_DecodeFrame(self.handle,
-                pcm.as_mut_ptr() as *mut i16,
+        self.decode_frame_with_flags(pcm, 0)
+    }
+
+    /// Synthesizes one frame of output for a lost access unit, eg. a missing
+    /// RTP packet, without consuming input. Call it once per lost access
+    /// unit in place of `fill` and `decode_frame` to keep the output
+    /// gapless:
+    ///
+    /// ```
+    /// use fdk_aac::dec;
+    ///
+    /// fn decode_packets(packets: &[Option<Vec<u8>>]) -> Result<Vec<i16>, dec::DecoderError> {
+    ///     let mut decoder = dec::Decoder::new(dec::Transport::Adts)?;
+    ///     decoder.set_conceal_method(dec::ConcealMethod::EnergyInterpolation)?;
+    ///
+    ///     let mut pcm = vec![0i16; 2048];
+    ///     let mut output = Vec::new();
+    ///
+    ///     for packet in packets {
+    ///         match packet {
+    ///             Some(packet) => {
+    ///                 decoder.fill(packet)?;
+    ///                 decoder.decode_frame(&mut pcm)?;
+    ///             }
+    ///             // lost packet
+    ///             None => decoder.conceal_frame(&mut pcm)?,
+    ///         }
+    ///
+    ///         output.extend_from_slice(&pcm[..decoder.decoded_frame_size()]);
+    ///     }
+    ///
+    ///     Ok(output)
+    /// }
+    /// ```
+    pub fn conceal_frame(&mut self, pcm: &mut [i16]) -> Result<(), DecoderError> {
+        self.decode_frame_with_flags(pcm, sys::AACDEC_CONCEAL)
+    }
+
+    /// Produces one frame of output holding the audio still delayed in the
+    /// filter banks at the end of the stream. Input not yet decoded is
+    /// discarded. The first `StreamInfo::output_delay` samples per channel
+    /// of the flushed frame are the remaining audio.
+    pub fn flush(&mut self, pcm: &mut [i16]) -> Result<(), DecoderError> {
+        self.decode_frame_with_flags(pcm, sys::AACDEC_FLUSH)
+    }
+
+    /// Prepares the decoder for input which does not continue the previous
+    /// input, eg. after seeking. Clears the transport buffer immediately and
+    /// the signal history with the next decoded frame, so audio from before
+    /// the discontinuity does not bleed into the output.
+    pub fn seek_reset(&mut self) -> Result<(), DecoderError> {
+        self.apply_param(sys::AACDEC_PARAM_AAC_TPDEC_CLEAR_BUFFER, 1)?;
+        self.pending_flags = sys::AACDEC_INTR | sys::AACDEC_CLRHIST;
+        Ok(())
+    }
+
+    fn decode_frame_with_flags(&mut self, pcm: &mut [i16], flags: u32) -> Result<(), DecoderError> {
+        let result = check(unsafe {
+            sys::aacDecoder_DecodeFrame(self.handle.as_ptr(),
+                pcm.as_mut_ptr(),
                 pcm.len() as c_int,
-                0))
+                flags | self.pending_flags)
+        });
+
+        if let Err(e) = result {
+            if !e.output_valid() {
+                return result;
+            }
         }
+
+        self.pending_flags = 0;
+
+        if let Some(mut reorder) = self.reorder.take() {
+            let frame_size = cmp::min(self.decoded_frame_size(), pcm.len());
+
+            reorder.update(raw_channels(self.raw_stream_info()));
+            reorder.apply(&mut pcm[..frame_size]);
+
+            self.reorder = Some(reorder);
+        }
+
+        result
+    }
+
+    /// Makes `decode_frame` reorder its output into the speaker order given,
+    /// eg. `Speaker::SMPTE_ORDER`. Pass `None` to keep the decoder's native
+    /// channel order. The mapping is recomputed only when the decoder's
+    /// channel layout changes.
+    pub fn set_channel_order(&mut self, order: Option<&[Speaker]>) {
+        self.reorder = order.map(ChannelReorder::new);
+    }
+
+    /// Registers a decoder owned buffer of `size` bytes which receives the
+    /// ancillary data elements of each decoded frame. After `decode_frame`
+    /// the elements are available through `ancillary_data`.
+    pub fn enable_ancillary_data(&mut self, size: usize) -> Result<(), DecoderError> {
+        let mut buffer = vec![0u8; size].into_boxed_slice();
+
+        unsafe {
+            check(sys::aacDecoder_AncDataInit(self.handle.as_ptr(),
+                buffer.as_mut_ptr(),
+                buffer.len() as c_int))?;
+        }
+
+        self.ancillary = Some(buffer);
+        Ok(())
+    }
+
+    /// Returns an iterator over the ancillary data elements of the most
+    /// recently decoded frame. Empty unless `enable_ancillary_data` was
+    /// called.
+    pub fn ancillary_data(&self) -> AncillaryData<'_> {
+        AncillaryData { decoder: self, index: 0 }
     }
 
     pub fn decoded_frame_size(&self) -> usize {
-        let stream_info = self.stream_info();
+        let stream_info = self.raw_stream_info();
 
         stream_info.numChannels as usize * stream_info.frameSize as usize
     }
 
-    pub fn stream_info(&self) -> &StreamInfo {
-        unsafe { &*sys::aacDecoder_GetStreamInfo(self.handle) }
+    /// Returns a snapshot of the decoder's view of the stream as of the most
+    /// recently decoded frame.
+    pub fn stream_info(&self) -> StreamInfo {
+        let mut info = StreamInfo::from_sys(self.raw_stream_info());
+        info.limiter_delay = self.limiter.delay(&info);
+        info
+    }
+
+    fn raw_stream_info(&self) -> &sys::CStreamInfo {
+        // libfdk-aac only returns NULL for a NULL handle, and the stream info
+        // lives as long as the decoder instance.
+        let info = unsafe { sys::aacDecoder_GetStreamInfo(self.handle.as_ptr()) };
+
+        unsafe { info.as_ref() }.expect("aacDecoder_GetStreamInfo returned NULL")
     }
 }
 
 impl Drop for Decoder {
     fn drop(&mut self) {
-        unsafe { sys::aacDecoder_Close(self.handle); }
+        unsafe { sys::aacDecoder_Close(self.handle.as_ptr()); }
+    }
+}
+
+/// Output of dual mono streams, eg. bilingual broadcasts carrying one
+/// language per channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DualChannelOutputMode {
+    /// Output both channels unchanged.
+    ///
+    /// Value: 0
+    Both = 0,
+    /// Output channel 1 on both channels.
+    ///
+    /// Value: 1
+    Channel1 = 1,
+    /// Output channel 2 on both channels.
+    ///
+    /// Value: 2
+    Channel2 = 2,
+    /// Output a mix of both channels on both channels.
+    ///
+    /// Value: 3
+    Mix = 3,
+}
+
+/// Order of the output channels, see `StreamInfo::channels` for the
+/// resulting layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelMapping {
+    /// MPEG order, as the channel elements appear in the bitstream.
+    ///
+    /// Value: 0
+    Mpeg = 0,
+    /// WAV file order, eg. L R C LFE Ls Rs for 5.1.
+    ///
+    /// Value: 1
+    Wav = 1,
+}
+
+/// Downmix and channel mapping configuration for `Decoder::set_downmix`.
+/// Settings left at `None` keep their current value.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DownmixConfig {
+    /// `None` keeps the current setting, initially `Both`.
+    pub dual_channel_output_mode: Option<DualChannelOutputMode>,
+    /// `None` keeps the current setting, initially `Wav`.
+    pub channel_mapping: Option<ChannelMapping>,
+    /// Upmix streams with fewer channels to at least this many output
+    /// channels. `None` keeps the current setting, initially `Stream`.
+    pub min_output_channels: Option<OutputChannels>,
+    /// Downmix streams with more channels to at most this many output
+    /// channels, using the downmix metadata in the stream if present.
+    /// `None` keeps the current setting, initially `Stream`.
+    pub max_output_channels: Option<OutputChannels>,
+}
+
+/// Output channel limit for `DownmixConfig`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputChannels {
+    /// Output the number of channels in the stream.
+    ///
+    /// Value: -1
+    Stream,
+    /// Output this many channels, one of 1, 2, 6 or 8.
+    ///
+    /// Value: 1, 2, 6 or 8
+    Count(usize),
+}
+
+impl OutputChannels {
+    fn to_sys(self) -> Result<i32, DecoderError> {
+        match self {
+            OutputChannels::Stream => Ok(-1),
+            OutputChannels::Count(channels @ 1)
+            | OutputChannels::Count(channels @ 2)
+            | OutputChannels::Count(channels @ 6)
+            | OutputChannels::Count(channels @ 8) => Ok(channels as i32),
+            OutputChannels::Count(_) => Err(DecoderError::SET_PARAM_FAIL),
+        }
+    }
+}
+
+/// Presentation mode to assume for streams which do not indicate one, see
+/// ETSI TS 101 154 C.5.4.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrcDefaultPresentationMode {
+    /// Apply all DRC parameters as requested, regardless of presentation
+    /// mode.
+    ///
+    /// Value: -1
+    ParameterHandlingDisabled = -1,
+    /// Adjust the DRC parameters to the presentation mode indicated in the
+    /// stream.
+    ///
+    /// Value: 0
+    ParameterHandlingEnabled = 0,
+    /// Value: 1
+    Mode1 = 1,
+    /// Value: 2
+    Mode2 = 2,
+}
+
+/// Dynamic range control configuration for `Decoder::set_drc`. Settings
+/// left at `None` keep their current value.
+///
+/// A night mode applies the full DRC gains and heavy compression, with
+/// loudness normalized to eg. -24 LKFS:
+///
+/// ```
+/// use fdk_aac::dec::{DrcConfig, DrcReferenceLevel};
+///
+/// let night_mode = DrcConfig {
+///     boost_factor: Some(1.0),
+///     attenuation_factor: Some(1.0),
+///     reference_level: Some(DrcReferenceLevel::Level(-24.0)),
+///     heavy_compression: Some(true),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DrcConfig {
+    /// Scaling of the boosting DRC gains, 0.0 (off) to 1.0 (full). `None`
+    /// keeps the current setting.
+    pub boost_factor: Option<f32>,
+    /// Scaling of the attenuating DRC gains, 0.0 (off) to 1.0 (full).
+    /// `None` keeps the current setting.
+    pub attenuation_factor: Option<f32>,
+    /// Target loudness of the output. MPEG-4 DRC, including
+    /// `heavy_compression`, is only applied while a level is set. `None`
+    /// keeps the current setting.
+    pub reference_level: Option<DrcReferenceLevel>,
+    /// Applies the ETSI heavy compression gains instead of the light
+    /// compression gains. `None` keeps the current setting.
+    pub heavy_compression: Option<bool>,
+    /// `None` keeps the current setting.
+    pub default_presentation_mode: Option<DrcDefaultPresentationMode>,
+    /// Target level in dB the encoder assumed when computing the DRC gains,
+    /// -31.75 to 0 in steps of 0.25. `None` keeps the current setting.
+    pub enc_target_level: Option<f32>,
+    /// MPEG-D DRC effect to select a DRC set for, used by xHE-AAC streams.
+    /// `None` keeps the current setting.
+    pub effect: Option<DrcEffect>,
+    /// Applies the album loudness of MPEG-D DRC metadata instead of the
+    /// track loudness. `None` keeps the current setting.
+    pub album_mode: Option<bool>,
+}
+
+impl DrcConfig {
+    fn validate(&self) -> Result<(), DecoderError> {
+        let factors_valid = self.boost_factor.iter()
+            .chain(&self.attenuation_factor)
+            .all(|factor| (0.0..=1.0).contains(factor));
+
+        let level_valid = self.enc_target_level.iter().all(|db| (-31.75..=0.0).contains(db));
+
+        if !factors_valid || !level_valid {
+            return Err(DecoderError::SET_PARAM_FAIL);
+        }
+
+        self.reference_level.map(DrcReferenceLevel::to_sys).transpose()?;
+        Ok(())
+    }
+}
+
+/// Target loudness for `DrcConfig::reference_level`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrcReferenceLevel {
+    /// Disable loudness normalization and MPEG-4 DRC. This is the library
+    /// default for MPEG-4 DRC.
+    ///
+    /// Value: -1
+    Off,
+    /// Normalize the output to this level in dB below full scale, -10 to
+    /// -31.75 in steps of 0.25.
+    ///
+    /// Value: 40 to 127
+    Level(f32),
+}
+
+impl DrcReferenceLevel {
+    fn to_sys(self) -> Result<i32, DecoderError> {
+        match self {
+            DrcReferenceLevel::Off => Ok(-1),
+            DrcReferenceLevel::Level(db) => match drc_level(db) {
+                level @ 40..=127 => Ok(level),
+                _ => Err(DecoderError::SET_PARAM_FAIL),
+            },
+        }
+    }
+}
+
+/// Requested MPEG-D DRC effect, see ISO/IEC 23003-4 Table A.10.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrcEffect {
+    /// Disable MPEG-D DRC completely.
+    ///
+    /// Value: -1
+    Off = -1,
+    /// No effect, but DRC is still applied where needed to prevent
+    /// clipping. This is the library default.
+    ///
+    /// Value: 0
+    None = 0,
+    /// Value: 1
+    LateNight = 1,
+    /// Value: 2
+    NoisyEnvironment = 2,
+    /// Value: 3
+    LimitedPlaybackRange = 3,
+    /// Value: 4
+    LowPlaybackLevel = 4,
+    /// Value: 5
+    DialogEnhancement = 5,
+    /// Enable MPEG-D DRC without requesting a particular effect.
+    ///
+    /// Value: 6
+    GeneralCompression = 6,
+}
+
+fn drc_factor(factor: f32) -> i32 {
+    (factor * 127.0).round() as i32
+}
+
+fn drc_level(db: f32) -> i32 {
+    (-db * 4.0).round() as i32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimiterMode {
+    /// Enable the limiter for all but low delay audio object types.
+    ///
+    /// Value: -1
+    Auto = -1,
+    /// Value: 0
+    Disabled = 0,
+    /// Value: 1
+    Enabled = 1,
+}
+
+/// PCM limiter configuration for `Decoder::set_limiter`.
+#[derive(Clone, Copy, Debug)]
+pub struct LimiterConfig {
+    pub mode: LimiterMode,
+    /// Attack time in ms, 1 to 15. Determines the delay added by the
+    /// limiter.
+    pub attack_time: u32,
+    /// Release time in ms, greater than 0.
+    pub release_time: u32,
+}
+
+impl LimiterConfig {
+    // checked before any parameter is set, so a rejected config cannot
+    // leave libfdk-aac running a limiter other than the recorded one. The
+    // limiter is opened with the maximum attack time of 15ms.
+    fn validate(&self) -> Result<(), DecoderError> {
+        if self.attack_time < 1 || self.attack_time > 15 || self.release_time == 0 {
+            return Err(DecoderError::SET_PARAM_FAIL);
+        }
+
+        Ok(())
+    }
+
+    // libfdk-aac delays the output of an active limiter by its attack time,
+    // and adds this delay to the reported output delay.
+    fn delay(&self, info: &StreamInfo) -> usize {
+        let enabled = match self.mode {
+            LimiterMode::Auto => !matches!(info.aot,
+                AudioObjectType::ErAacLowDelay | AudioObjectType::ErAacEnhancedLowDelay),
+            LimiterMode::Disabled => false,
+            LimiterMode::Enabled => true,
+        };
+
+        if enabled {
+            self.attack_time as usize * info.sample_rate as usize / 1000
+        } else {
+            0
+        }
+    }
+}
+
+impl Default for LimiterConfig {
+    fn default() -> Self {
+        LimiterConfig {
+            mode: LimiterMode::Auto,
+            attack_time: 15,
+            release_time: 50,
+        }
+    }
+}
+
+/// Error concealment method, used for lost and corrupt access units.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConcealMethod {
+    /// Mute the spectral data of the lost frame.
+    ///
+    /// Value: 0
+    SpectralMuting = 0,
+    /// Fill the lost frame with noise shaped like the previous frame.
+    ///
+    /// Value: 1
+    NoiseSubstitution = 1,
+    /// Interpolate the spectral energy between the surrounding frames. Adds
+    /// one frame of output delay. This is the library default.
+    ///
+    /// Value: 2
+    EnergyInterpolation = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioObjectType {
+    /// No audio object type, eg. before the first frame was decoded.
+    None,
+    AacMain,
+    AacLowComplexity,
+    AacScalableSampleRate,
+    AacLongTermPrediction,
+    /// Spectral Band Replication.
+    Sbr,
+    AacScalable,
+    ErAacLowComplexity,
+    ErAacLongTermPrediction,
+    ErAacScalable,
+    ErBsac,
+    ErAacLowDelay,
+    /// Parametric Stereo.
+    Ps,
+    MpegSurround,
+    ErAacEnhancedLowDelay,
+    /// Unified Speech and Audio Coding (xHE-AAC).
+    Usac,
+    LowDelayMpegSurround,
+    Mpeg2AacLowComplexity,
+    Mpeg2Sbr,
+    DrmAac,
+    DrmSbr,
+    DrmMpegPs,
+    DrmSurround,
+    DrmUsac,
+    /// Any other audio object type value.
+    Other(i32),
+}
+
+impl AudioObjectType {
+    fn from_sys(aot: sys::AUDIO_OBJECT_TYPE) -> Self {
+        match aot {
+            sys::AUDIO_OBJECT_TYPE_AOT_NONE | sys::AUDIO_OBJECT_TYPE_AOT_NULL_OBJECT => AudioObjectType::None,
+            sys::AUDIO_OBJECT_TYPE_AOT_AAC_MAIN => AudioObjectType::AacMain,
+            sys::AUDIO_OBJECT_TYPE_AOT_AAC_LC => AudioObjectType::AacLowComplexity,
+            sys::AUDIO_OBJECT_TYPE_AOT_AAC_SSR => AudioObjectType::AacScalableSampleRate,
+            sys::AUDIO_OBJECT_TYPE_AOT_AAC_LTP => AudioObjectType::AacLongTermPrediction,
+            sys::AUDIO_OBJECT_TYPE_AOT_SBR => AudioObjectType::Sbr,
+            sys::AUDIO_OBJECT_TYPE_AOT_AAC_SCAL => AudioObjectType::AacScalable,
+            sys::AUDIO_OBJECT_TYPE_AOT_ER_AAC_LC => AudioObjectType::ErAacLowComplexity,
+            sys::AUDIO_OBJECT_TYPE_AOT_ER_AAC_LTP => AudioObjectType::ErAacLongTermPrediction,
+            sys::AUDIO_OBJECT_TYPE_AOT_ER_AAC_SCAL => AudioObjectType::ErAacScalable,
+            sys::AUDIO_OBJECT_TYPE_AOT_ER_BSAC => AudioObjectType::ErBsac,
+            sys::AUDIO_OBJECT_TYPE_AOT_ER_AAC_LD => AudioObjectType::ErAacLowDelay,
+            sys::AUDIO_OBJECT_TYPE_AOT_PS => AudioObjectType::Ps,
+            sys::AUDIO_OBJECT_TYPE_AOT_MPEGS => AudioObjectType::MpegSurround,
+            sys::AUDIO_OBJECT_TYPE_AOT_ER_AAC_ELD => AudioObjectType::ErAacEnhancedLowDelay,
+            sys::AUDIO_OBJECT_TYPE_AOT_USAC => AudioObjectType::Usac,
+            sys::AUDIO_OBJECT_TYPE_AOT_LD_MPEGS => AudioObjectType::LowDelayMpegSurround,
+            sys::AUDIO_OBJECT_TYPE_AOT_MP2_AAC_LC => AudioObjectType::Mpeg2AacLowComplexity,
+            sys::AUDIO_OBJECT_TYPE_AOT_MP2_SBR => AudioObjectType::Mpeg2Sbr,
+            sys::AUDIO_OBJECT_TYPE_AOT_DRM_AAC => AudioObjectType::DrmAac,
+            sys::AUDIO_OBJECT_TYPE_AOT_DRM_SBR => AudioObjectType::DrmSbr,
+            sys::AUDIO_OBJECT_TYPE_AOT_DRM_MPEG_PS => AudioObjectType::DrmMpegPs,
+            sys::AUDIO_OBJECT_TYPE_AOT_DRM_SURROUND => AudioObjectType::DrmSurround,
+            sys::AUDIO_OBJECT_TYPE_AOT_DRM_USAC => AudioObjectType::DrmUsac,
+            other => AudioObjectType::Other(other),
+        }
+    }
+}
+
+/// Speaker position of an output channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelType {
+    None,
+    Front,
+    Side,
+    Back,
+    Lfe,
+    Top,
+    FrontTop,
+    SideTop,
+    BackTop,
+    Bottom,
+    FrontBottom,
+    SideBottom,
+    BackBottom,
+}
+
+impl ChannelType {
+    fn from_sys(channel_type: sys::AUDIO_CHANNEL_TYPE) -> Self {
+        match channel_type {
+            sys::AUDIO_CHANNEL_TYPE_ACT_FRONT => ChannelType::Front,
+            sys::AUDIO_CHANNEL_TYPE_ACT_SIDE => ChannelType::Side,
+            sys::AUDIO_CHANNEL_TYPE_ACT_BACK => ChannelType::Back,
+            sys::AUDIO_CHANNEL_TYPE_ACT_LFE => ChannelType::Lfe,
+            sys::AUDIO_CHANNEL_TYPE_ACT_TOP => ChannelType::Top,
+            sys::AUDIO_CHANNEL_TYPE_ACT_FRONT_TOP => ChannelType::FrontTop,
+            sys::AUDIO_CHANNEL_TYPE_ACT_SIDE_TOP => ChannelType::SideTop,
+            sys::AUDIO_CHANNEL_TYPE_ACT_BACK_TOP => ChannelType::BackTop,
+            sys::AUDIO_CHANNEL_TYPE_ACT_BOTTOM => ChannelType::Bottom,
+            sys::AUDIO_CHANNEL_TYPE_ACT_FRONT_BOTTOM => ChannelType::FrontBottom,
+            sys::AUDIO_CHANNEL_TYPE_ACT_SIDE_BOTTOM => ChannelType::SideBottom,
+            sys::AUDIO_CHANNEL_TYPE_ACT_BACK_BOTTOM => ChannelType::BackBottom,
+            _ => ChannelType::None,
+        }
+    }
+}
+
+/// An output channel. `index` orders channels of the same type, counting
+/// from the center outwards, eg. front channels C, L, R have indices 0, 1, 2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Channel {
+    pub channel_type: ChannelType,
+    pub index: u8,
+}
+
+/// Owned snapshot of the decoder's stream information.
+#[derive(Clone, Debug)]
+pub struct StreamInfo {
+    /// Sample rate in Hz of the decoded PCM audio.
+    pub sample_rate: u32,
+    /// Samples per channel in one decoded PCM frame, eg. 1024 for AAC-LC and
+    /// 2048 for HE-AAC.
+    pub frame_size: usize,
+    /// Number of output channels.
+    pub num_channels: usize,
+    /// Speaker position of each output channel, in output order.
+    pub channels: Vec<Channel>,
+    /// Sample rate in Hz of the AAC core, ie. without SBR.
+    pub aac_sample_rate: u32,
+    /// Samples per channel in one frame of the AAC core.
+    pub aac_samples_per_frame: usize,
+    /// Number of channels after AAC core processing, before PS or MPS.
+    pub aac_num_channels: usize,
+    /// MPEG-2 profile from the ADTS/ADIF header, `None` for MPEG-4.
+    pub profile: Option<u32>,
+    pub aot: AudioObjectType,
+    /// Channel configuration from the AudioSpecificConfig, 0 if defined by a
+    /// program config element.
+    pub channel_config: u32,
+    /// Instantaneous bit rate.
+    pub bit_rate: u32,
+    pub extension_aot: AudioObjectType,
+    /// Sample rate in Hz of the extension, eg. the SBR output rate.
+    pub extension_sample_rate: u32,
+    /// Number of samples the output is additionally delayed by the decoder.
+    pub output_delay: usize,
+    /// Part of `output_delay` added by the PCM limiter, see
+    /// `Decoder::set_limiter`.
+    pub limiter_delay: usize,
+    /// Copy of the decoder's internal flags.
+    pub flags: u32,
+    /// Error protection configuration, `None` for non-ER audio object types.
+    pub ep_config: Option<u8>,
+    /// Estimated number of lost access units after a
+    /// `DecoderError::TRANSPORT_SYNC_ERROR`, negative if the estimation
+    /// failed.
+    pub num_lost_access_units: i32,
+    pub num_total_bytes: u64,
+    /// Number of bytes out of `num_total_bytes` considered erroneous.
+    pub num_bad_bytes: u64,
+    pub num_total_access_units: u64,
+    /// Number of access units out of `num_total_access_units` considered
+    /// erroneous.
+    pub num_bad_access_units: u64,
+    /// Programme reference level in dB the decoder used for the last frame,
+    /// `None` if the stream does not carry one.
+    pub drc_prog_ref_level: Option<f32>,
+    /// DRC presentation mode the decoder used for the last frame, 0 if not
+    /// indicated, 1 or 2. `None` if the stream carries no DRC metadata.
+    pub drc_presentation_mode: Option<u8>,
+}
+
+fn raw_channels(info: &sys::CStreamInfo) -> impl Iterator<Item = Channel> + Clone + '_ {
+    let num_channels = if info.numChannels > 0 { info.numChannels as usize } else { 0 };
+
+    let (types, indices): (&[sys::AUDIO_CHANNEL_TYPE], &[u8]) =
+        if num_channels > 0 && !info.pChannelType.is_null() && !info.pChannelIndices.is_null() {
+            unsafe {
+                (slice::from_raw_parts(info.pChannelType, num_channels),
+                    slice::from_raw_parts(info.pChannelIndices, num_channels))
+            }
+        } else {
+            (&[], &[])
+        };
+
+    types.iter().zip(indices)
+        .map(|(&channel_type, &index)| Channel {
+            channel_type: ChannelType::from_sys(channel_type),
+            index,
+        })
+}
+
+impl StreamInfo {
+    fn from_sys(info: &sys::CStreamInfo) -> Self {
+        let num_channels = if info.numChannels > 0 { info.numChannels as usize } else { 0 };
+
+        let channels = raw_channels(info).collect();
+
+        StreamInfo {
+            sample_rate: info.sampleRate as u32,
+            frame_size: info.frameSize as usize,
+            num_channels,
+            channels,
+            aac_sample_rate: info.aacSampleRate as u32,
+            aac_samples_per_frame: info.aacSamplesPerFrame as usize,
+            aac_num_channels: info.aacNumChannels as usize,
+            profile: if info.profile >= 0 { Some(info.profile as u32) } else { None },
+            aot: AudioObjectType::from_sys(info.aot),
+            channel_config: info.channelConfig as u32,
+            bit_rate: info.bitRate as u32,
+            extension_aot: AudioObjectType::from_sys(info.extAot),
+            extension_sample_rate: info.extSamplingRate as u32,
+            output_delay: info.outputDelay as usize,
+            limiter_delay: 0,
+            flags: info.flags,
+            ep_config: if info.epConfig >= 0 { Some(info.epConfig as u8) } else { None },
+            num_lost_access_units: info.numLostAccessUnits,
+            num_total_bytes: info.numTotalBytes as u64,
+            num_bad_bytes: info.numBadBytes as u64,
+            num_total_access_units: info.numTotalAccessUnits as u64,
+            num_bad_access_units: info.numBadAccessUnits as u64,
+            drc_prog_ref_level: if info.drcProgRefLev >= 0 { Some(info.drcProgRefLev as f32 / -4.0) } else { None },
+            drc_presentation_mode: if info.drcPresMode >= 0 { Some(info.drcPresMode as u8) } else { None },
+        }
+    }
+
+    /// Whether the stream is USAC (xHE-AAC). For USAC `aac_samples_per_frame`
+    /// is the core frame length of 768 or 1024 samples.
+    pub fn is_usac(&self) -> bool {
+        self.flags & sys::AC_USAC != 0
+    }
+
+    pub fn sbr_present(&self) -> bool {
+        self.flags & sys::AC_SBR_PRESENT != 0
+    }
+
+    pub fn ps_present(&self) -> bool {
+        self.flags & sys::AC_PS_PRESENT != 0
+    }
+
+    /// Whether MPEG Surround is present, eg. USAC stereo coded as MPS212.
+    pub fn mps_present(&self) -> bool {
+        self.flags & sys::AC_MPS_PRESENT != 0
+    }
+
+    /// Whether the stream carries MPEG-D DRC metadata, see
+    /// `DrcConfig::effect`.
+    pub fn mpegd_drc_present(&self) -> bool {
+        self.flags & sys::AC_DRC_PRESENT != 0
+    }
+
+    /// Speaker layout of the decoded PCM output.
+    pub fn channel_layout(&self) -> ChannelLayout {
+        ChannelLayout::new(&self.channels)
     }
 }
 
+/// Loudspeaker positions, declared in the bit order of the WAV
+/// `dwChannelMask` field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    FrontLeftOfCenter,
+    FrontRightOfCenter,
+    BackCenter,
+    SideLeft,
+    SideRight,
+    TopCenter,
+    TopFrontLeft,
+    TopFrontCenter,
+    TopFrontRight,
+    TopBackLeft,
+    TopBackCenter,
+    TopBackRight,
+}
+
+impl Speaker {
+    /// Channel order of WAVE_FORMAT_EXTENSIBLE files.
+    pub const WAV_ORDER: [Speaker; 18] = [
+        Speaker::FrontLeft,
+        Speaker::FrontRight,
+        Speaker::FrontCenter,
+        Speaker::LowFrequency,
+        Speaker::BackLeft,
+        Speaker::BackRight,
+        Speaker::FrontLeftOfCenter,
+        Speaker::FrontRightOfCenter,
+        Speaker::BackCenter,
+        Speaker::SideLeft,
+        Speaker::SideRight,
+        Speaker::TopCenter,
+        Speaker::TopFrontLeft,
+        Speaker::TopFrontCenter,
+        Speaker::TopFrontRight,
+        Speaker::TopBackLeft,
+        Speaker::TopBackCenter,
+        Speaker::TopBackRight,
+    ];
+
+    /// SMPTE ST 2036-2 channel order, ie. L R C LFE Ls Rs Lrs Rrs. Differs
+    /// from `WAV_ORDER` in placing side channels before back channels.
+    pub const SMPTE_ORDER: [Speaker; 18] = [
+        Speaker::FrontLeft,
+        Speaker::FrontRight,
+        Speaker::FrontCenter,
+        Speaker::LowFrequency,
+        Speaker::SideLeft,
+        Speaker::SideRight,
+        Speaker::BackLeft,
+        Speaker::BackRight,
+        Speaker::FrontLeftOfCenter,
+        Speaker::FrontRightOfCenter,
+        Speaker::BackCenter,
+        Speaker::TopCenter,
+        Speaker::TopFrontLeft,
+        Speaker::TopFrontCenter,
+        Speaker::TopFrontRight,
+        Speaker::TopBackLeft,
+        Speaker::TopBackCenter,
+        Speaker::TopBackRight,
+    ];
+
+    /// Bit of this speaker in the WAV `dwChannelMask` field.
+    pub fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Speaker layout of the decoder output, derived from the channel types and
+/// indices reported in the stream info.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelLayout {
+    speakers: Vec<Option<Speaker>>,
+}
+
+impl ChannelLayout {
+    pub fn new(channels: &[Channel]) -> Self {
+        let count = |channel_type| channels.iter()
+            .filter(|channel| channel.channel_type == channel_type)
+            .count();
+
+        let speakers = channels.iter()
+            .map(|channel| speaker_for(channel.channel_type, channel.index as usize, count(channel.channel_type)))
+            .collect();
+
+        ChannelLayout { speakers }
+    }
+
+    /// Speaker of each output channel, in decoder output order. `None` for
+    /// channels without a WAV speaker position, eg. bottom channels.
+    pub fn speakers(&self) -> &[Option<Speaker>] {
+        &self.speakers
+    }
+
+    /// WAV `dwChannelMask` of the speakers present in this layout.
+    pub fn channel_mask(&self) -> u32 {
+        self.speakers.iter().flatten().fold(0, |mask, speaker| mask | speaker.mask())
+    }
+
+    /// Returns for each channel position of the reordered output the index of
+    /// the decoder output channel to place there. Channels are sorted by the
+    /// position of their speaker in `order`. Channels not listed in `order`
+    /// follow in decoder output order.
+    pub fn mapping(&self, order: &[Speaker]) -> Vec<usize> {
+        let mut mapping = (0..self.speakers.len()).collect::<Vec<_>>();
+
+        mapping.sort_by_key(|&channel| {
+            self.speakers[channel]
+                .and_then(|speaker| order.iter().position(|&s| s == speaker))
+                .unwrap_or(order.len())
+        });
+
+        mapping
+    }
+
+    /// Reorders interleaved PCM in this layout in place into the order given
+    /// by `order`, see `mapping`. Trailing samples not forming a complete
+    /// frame are left untouched.
+    pub fn reorder(&self, order: &[Speaker], pcm: &mut [i16]) {
+        let mapping = self.mapping(order);
+        let mut frame = vec![0i16; mapping.len()];
+
+        reorder(&mapping, &mut frame, pcm);
+    }
+}
+
+fn reorder(mapping: &[usize], frame: &mut [i16], pcm: &mut [i16]) {
+    if mapping.iter().enumerate().all(|(pos, &channel)| pos == channel) {
+        return;
+    }
+
+    for samples in pcm.chunks_exact_mut(mapping.len()) {
+        frame.copy_from_slice(samples);
+
+        for (sample, &channel) in samples.iter_mut().zip(mapping) {
+            *sample = frame[channel];
+        }
+    }
+}
+
+// Channel reordering applied by `Decoder::decode_frame`, caching the mapping
+// for the most recent decoder channel layout.
+#[derive(Debug)]
+struct ChannelReorder {
+    order: Vec<Speaker>,
+    channels: Vec<Channel>,
+    mapping: Vec<usize>,
+    frame: Vec<i16>,
+}
+
+impl ChannelReorder {
+    fn new(order: &[Speaker]) -> Self {
+        ChannelReorder {
+            order: order.to_vec(),
+            channels: Vec::new(),
+            mapping: Vec::new(),
+            frame: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, channels: impl Iterator<Item = Channel> + Clone) {
+        if channels.clone().eq(self.channels.iter().copied()) {
+            return;
+        }
+
+        self.channels = channels.collect();
+        self.mapping = ChannelLayout::new(&self.channels).mapping(&self.order);
+        self.frame = vec![0i16; self.mapping.len()];
+    }
+
+    fn apply(&mut self, pcm: &mut [i16]) {
+        reorder(&self.mapping, &mut self.frame, pcm);
+    }
+}
+
+// Front and back channel indices count pairs from the center outwards, with
+// the center channel of an odd count first for front and last for back
+// channels, see ISO/IEC 13818-7 8.5.3.2.
+fn speaker_for(channel_type: ChannelType, index: usize, count: usize) -> Option<Speaker> {
+    let has_center = count % 2 == 1;
+
+    match channel_type {
+        ChannelType::Front => {
+            if has_center && index == 0 {
+                return Some(Speaker::FrontCenter);
+            }
+
+            let index = index - has_center as usize;
+            let pair_from_outside = (count / 2).checked_sub(index / 2 + 1)?;
+
+            match (pair_from_outside, index % 2) {
+                (0, 0) => Some(Speaker::FrontLeft),
+                (0, _) => Some(Speaker::FrontRight),
+                (1, 0) => Some(Speaker::FrontLeftOfCenter),
+                (1, _) => Some(Speaker::FrontRightOfCenter),
+                _ => None,
+            }
+        }
+        ChannelType::Side => match index {
+            0 => Some(Speaker::SideLeft),
+            1 => Some(Speaker::SideRight),
+            _ => None,
+        },
+        ChannelType::Back => {
+            if has_center && index == count - 1 {
+                return Some(Speaker::BackCenter);
+            }
+
+            match index {
+                0 => Some(Speaker::BackLeft),
+                1 => Some(Speaker::BackRight),
+                _ => None,
+            }
+        }
+        ChannelType::Lfe if index == 0 => Some(Speaker::LowFrequency),
+        ChannelType::Top if index == 0 => Some(Speaker::TopCenter),
+        ChannelType::FrontTop => match (has_center, index) {
+            (true, 0) => Some(Speaker::TopFrontCenter),
+            (true, 1) | (false, 0) => Some(Speaker::TopFrontLeft),
+            (true, 2) | (false, 1) => Some(Speaker::TopFrontRight),
+            _ => None,
+        },
+        ChannelType::BackTop => {
+            if has_center && index == count - 1 {
+                return Some(Speaker::TopBackCenter);
+            }
+
+            match index {
+                0 => Some(Speaker::TopBackLeft),
+                1 => Some(Speaker::TopBackRight),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct AncillaryData<'a> {
+    decoder: &'a Decoder,
+    index: c_int,
+}
+
+impl<'a> Iterator for AncillaryData<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        self.decoder.ancillary.as_ref()?;
+
+        let mut ptr: *mut u8 = ptr::null_mut();
+        let mut size: c_int = 0;
+
+        unsafe {
+            check(sys::aacDecoder_AncDataGet(self.decoder.handle.as_ptr(),
+                self.index,
+                &mut ptr as *mut _,
+                &mut size as *mut _)).ok()?;
+        }
+
+        if ptr.is_null() || size < 0 {
+            return None;
+        }
+
+        self.index += 1;
+
+        // the element points into the registered buffer, which lives as long
+        // as the decoder and is only rewritten by decode_frame
+        Some(unsafe { slice::from_raw_parts(ptr, size as usize) })
+    }
+}
+
+/// Transport format of the bitstream passed to `Decoder::fill`.
 #[derive(Clone, Copy, Debug)]
 pub enum Transport {
+    /// Raw access units, configured out-of-band through `config_raw`.
+    ///
+    /// Value: 0
     Raw,
+    /// Audio Data Transport Stream.
+    ///
+    /// Value: 2
     Adts,
+    /// Audio Data Interchange Format, a single header at the start of the
+    /// stream.
+    ///
+    /// Value: 1
+    Adif,
+    /// LATM audio mux elements with in-band StreamMuxConfig
+    /// (muxConfigPresent = 1). Each `fill` must provide whole audio mux
+    /// elements, as LATM has no sync layer.
+    ///
+    /// Value: 6
+    LatmMcp1,
+    /// LATM audio mux elements with out-of-band StreamMuxConfig
+    /// (muxConfigPresent = 0), configured through `config_raw`.
+    ///
+    /// Value: 7
+    LatmMcp0,
+    /// LATM wrapped in the LOAS audio sync stream, as used in DVB.
+    ///
+    /// Value: 10
+    Loas,
+    /// Digital Radio Mondiale, configured out-of-band through `config_raw`
+    /// with the DRM SDC audio information.
+    ///
+    /// Value: 12
+    Drm,
+}
+
+impl Transport {
+    fn to_sys(self) -> sys::TRANSPORT_TYPE {
+        match self {
+            Transport::Raw => sys::TRANSPORT_TYPE_TT_MP4_RAW,
+            Transport::Adts => sys::TRANSPORT_TYPE_TT_MP4_ADTS,
+            Transport::Adif => sys::TRANSPORT_TYPE_TT_MP4_ADIF,
+            Transport::LatmMcp1 => sys::TRANSPORT_TYPE_TT_MP4_LATM_MCP1,
+            Transport::LatmMcp0 => sys::TRANSPORT_TYPE_TT_MP4_LATM_MCP0,
+            Transport::Loas => sys::TRANSPORT_TYPE_TT_MP4_LOAS,
+            Transport::Drm => sys::TRANSPORT_TYPE_TT_DRM,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(channels: &[(ChannelType, u8)]) -> ChannelLayout {
+        let channels = channels.iter()
+            .map(|&(channel_type, index)| Channel { channel_type, index })
+            .collect::<Vec<_>>();
+
+        ChannelLayout::new(&channels)
+    }
+
+    #[test]
+    fn surround_5_1() {
+        use ChannelType::*;
+
+        // MPEG order: C L R Ls Rs LFE
+        let layout = layout(&[(Front, 0), (Front, 1), (Front, 2), (Back, 0), (Back, 1), (Lfe, 0)]);
+
+        assert_eq!(layout.speakers(), &[
+            Some(Speaker::FrontCenter),
+            Some(Speaker::FrontLeft),
+            Some(Speaker::FrontRight),
+            Some(Speaker::BackLeft),
+            Some(Speaker::BackRight),
+            Some(Speaker::LowFrequency),
+        ]);
+
+        assert_eq!(layout.channel_mask(), 0x3f);
+        assert_eq!(layout.mapping(&Speaker::WAV_ORDER), vec![1, 2, 0, 5, 3, 4]);
+        assert_eq!(layout.mapping(&Speaker::SMPTE_ORDER), vec![1, 2, 0, 5, 3, 4]);
+
+        let mut pcm = vec![0, 1, 2, 3, 4, 5, 10, 11, 12, 13, 14, 15, 99];
+        layout.reorder(&Speaker::WAV_ORDER, &mut pcm);
+        assert_eq!(pcm, vec![1, 2, 0, 5, 3, 4, 11, 12, 10, 15, 13, 14, 99]);
+    }
+
+    #[test]
+    fn surround_7_1_front_center() {
+        use ChannelType::*;
+
+        // C Lc Rc L R Ls Rs LFE
+        let layout = layout(&[
+            (Front, 0), (Front, 1), (Front, 2), (Front, 3), (Front, 4),
+            (Back, 0), (Back, 1), (Lfe, 0),
+        ]);
+
+        assert_eq!(layout.speakers(), &[
+            Some(Speaker::FrontCenter),
+            Some(Speaker::FrontLeftOfCenter),
+            Some(Speaker::FrontRightOfCenter),
+            Some(Speaker::FrontLeft),
+            Some(Speaker::FrontRight),
+            Some(Speaker::BackLeft),
+            Some(Speaker::BackRight),
+            Some(Speaker::LowFrequency),
+        ]);
+
+        assert_eq!(layout.channel_mask(), 0xff);
+        assert_eq!(layout.mapping(&Speaker::WAV_ORDER), vec![3, 4, 0, 7, 5, 6, 1, 2]);
+    }
+
+    #[test]
+    fn surround_7_1_rear() {
+        use ChannelType::*;
+
+        // C L R Ls Rs Lrear Rrear LFE
+        let layout = layout(&[
+            (Front, 0), (Front, 1), (Front, 2), (Side, 0), (Side, 1),
+            (Back, 0), (Back, 1), (Lfe, 0),
+        ]);
+
+        assert_eq!(layout.speakers(), &[
+            Some(Speaker::FrontCenter),
+            Some(Speaker::FrontLeft),
+            Some(Speaker::FrontRight),
+            Some(Speaker::SideLeft),
+            Some(Speaker::SideRight),
+            Some(Speaker::BackLeft),
+            Some(Speaker::BackRight),
+            Some(Speaker::LowFrequency),
+        ]);
+
+        assert_eq!(layout.channel_mask(), 0x63f);
+        assert_eq!(layout.mapping(&Speaker::WAV_ORDER), vec![1, 2, 0, 7, 5, 6, 3, 4]);
+        assert_eq!(layout.mapping(&Speaker::SMPTE_ORDER), vec![1, 2, 0, 7, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn unmapped_channels_keep_decoder_order() {
+        use ChannelType::*;
+
+        let layout = layout(&[(Bottom, 0), (Front, 0), (Front, 1), (SideTop, 0)]);
+
+        assert_eq!(layout.speakers(), &[Option::None, Some(Speaker::FrontLeft), Some(Speaker::FrontRight), Option::None]);
+        assert_eq!(layout.mapping(&Speaker::WAV_ORDER), vec![1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn cached_reorder_follows_layout_changes() {
+        use ChannelType::*;
+
+        let stereo = [Channel { channel_type: Front, index: 0 }, Channel { channel_type: Front, index: 1 }];
+        let surround = [
+            Channel { channel_type: Front, index: 0 },
+            Channel { channel_type: Front, index: 1 },
+            Channel { channel_type: Front, index: 2 },
+        ];
+
+        let mut reorder = ChannelReorder::new(&Speaker::WAV_ORDER);
+
+        reorder.update(surround.iter().copied());
+        let mut pcm = vec![0, 1, 2];
+        reorder.apply(&mut pcm);
+        assert_eq!(pcm, vec![1, 2, 0]);
+
+        reorder.update(stereo.iter().copied());
+        let mut pcm = vec![0, 1];
+        reorder.apply(&mut pcm);
+        assert_eq!(pcm, vec![0, 1]);
+    }
+
+    #[test]
+    fn decode_errors_have_valid_output() {
+        assert!(DecoderError::CRC_ERROR.output_valid());
+        assert!(DecoderError::PARSE_ERROR.output_valid());
+        assert!(DecoderError::ANC_DATA_ERROR.output_valid());
+        assert!(!DecoderError::NOT_ENOUGH_BITS.output_valid());
+        assert!(!DecoderError::NEED_TO_RESTART.output_valid());
+    }
+
+    #[test]
+    fn output_channels_are_range_checked() {
+        assert_eq!(OutputChannels::Stream.to_sys(), Ok(-1));
+
+        for &channels in [1, 2, 6, 8].iter() {
+            assert_eq!(OutputChannels::Count(channels).to_sys(), Ok(channels as i32));
+        }
+
+        for &channels in [0, 3, 4, 5, 7, 9].iter() {
+            assert_eq!(OutputChannels::Count(channels).to_sys(), Err(DecoderError::SET_PARAM_FAIL));
+        }
+    }
+
+    #[test]
+    fn drc_config_is_range_checked() {
+        let invalid = [
+            DrcConfig { boost_factor: Some(-0.1), ..Default::default() },
+            DrcConfig { boost_factor: Some(1.1), ..Default::default() },
+            DrcConfig { boost_factor: Some(f32::NAN), ..Default::default() },
+            DrcConfig { attenuation_factor: Some(-0.1), ..Default::default() },
+            DrcConfig { attenuation_factor: Some(1.1), ..Default::default() },
+            DrcConfig { attenuation_factor: Some(f32::NAN), ..Default::default() },
+            DrcConfig { enc_target_level: Some(0.25), ..Default::default() },
+            DrcConfig { enc_target_level: Some(-32.0), ..Default::default() },
+            DrcConfig { enc_target_level: Some(f32::NAN), ..Default::default() },
+            DrcConfig { reference_level: Some(DrcReferenceLevel::Level(-9.75)), ..Default::default() },
+            DrcConfig { reference_level: Some(DrcReferenceLevel::Level(f32::NAN)), ..Default::default() },
+        ];
+
+        for config in invalid.iter() {
+            assert_eq!(config.validate(), Err(DecoderError::SET_PARAM_FAIL), "{:?}", config);
+        }
+
+        let valid = [
+            DrcConfig::default(),
+            DrcConfig {
+                boost_factor: Some(0.0),
+                attenuation_factor: Some(1.0),
+                enc_target_level: Some(-31.75),
+                reference_level: Some(DrcReferenceLevel::Level(-10.0)),
+                ..Default::default()
+            },
+            DrcConfig {
+                boost_factor: Some(1.0),
+                attenuation_factor: Some(0.0),
+                enc_target_level: Some(0.0),
+                reference_level: Some(DrcReferenceLevel::Off),
+                ..Default::default()
+            },
+        ];
+
+        for config in valid.iter() {
+            assert_eq!(config.validate(), Ok(()), "{:?}", config);
+        }
+    }
 }
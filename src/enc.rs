@@ -6,11 +6,34 @@ use std::ptr;
 
 use fdk_aac_sys as sys;
 
+use crate::asc::AudioSpecificConfig;
+
 pub use sys::AACENC_InfoStruct as InfoStruct;
 
+// error code outside the range used by libfdk-aac, for input this crate
+// rejects before passing it to the library
+const INVALID_INPUT_LENGTH: sys::AACENC_ERROR = 0x100;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct EncoderError(sys::AACENC_ERROR);
 
 impl EncoderError {
+    pub const INVALID_HANDLE: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INVALID_HANDLE);
+    pub const MEMORY_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_MEMORY_ERROR);
+    pub const UNSUPPORTED_PARAMETER: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_UNSUPPORTED_PARAMETER);
+    pub const INVALID_CONFIG: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INVALID_CONFIG);
+    pub const INIT_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INIT_ERROR);
+    pub const INIT_AAC_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INIT_AAC_ERROR);
+    pub const INIT_SBR_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INIT_SBR_ERROR);
+    pub const INIT_TP_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INIT_TP_ERROR);
+    pub const INIT_META_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INIT_META_ERROR);
+    pub const INIT_MPS_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_INIT_MPS_ERROR);
+    pub const ENCODE_ERROR: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_ENCODE_ERROR);
+    pub const ENCODE_EOF: EncoderError = EncoderError(sys::AACENC_ERROR_AACENC_ENCODE_EOF);
+    /// The input length is not a multiple of the configured channel count.
+    /// Raised by this crate rather than libfdk-aac.
+    pub const INVALID_INPUT_LENGTH: EncoderError = EncoderError(INVALID_INPUT_LENGTH);
+
     fn message(&self) -> &'static str {
         match self.0 {
             sys::AACENC_ERROR_AACENC_OK => "Ok",
@@ -26,6 +49,7 @@ impl EncoderError {
             sys::AACENC_ERROR_AACENC_INIT_MPS_ERROR => "MPS library initialization error.",
             sys::AACENC_ERROR_AACENC_ENCODE_ERROR => "The encoding process was interrupted by an unexpected error.",
             sys::AACENC_ERROR_AACENC_ENCODE_EOF => "End of file reached.",
+            INVALID_INPUT_LENGTH => "Input length is not a multiple of the channel count.",
             _ => "Unknown error",
         }
     }
@@ -55,8 +79,10 @@ struct EncoderHandle {
     ptr: sys::HANDLE_AACENCODER,
 }
 
+// The handle is owned exclusively, so it can move between threads. It is
+// not Sync: some queries through a shared reference write to the handle,
+// eg. aacEncInfo rebuilds the StreamMuxConfig for LATM/LOAS.
 unsafe impl Send for EncoderHandle {}
-unsafe impl Sync for EncoderHandle {}
 
 impl EncoderHandle {
     pub fn alloc(max_modules: usize, max_channels: usize) -> Result<Self, EncoderError> {
@@ -66,6 +92,58 @@ impl EncoderHandle {
         })?;
         Ok(EncoderHandle { ptr })
     }
+
+    fn set_param(&mut self, param: sys::AACENC_PARAM, value: u32) -> Result<(), EncoderError> {
+        check(unsafe { sys::aacEncoder_SetParam(self.ptr, param, value) })
+    }
+
+    fn get_param(&self, param: sys::AACENC_PARAM) -> u32 {
+        unsafe { sys::aacEncoder_GetParam(self.ptr, param) }
+    }
+
+    fn set_bit_rate(&mut self, bit_rate: BitRate) -> Result<(), EncoderError> {
+        let bitrate_mode = match bit_rate {
+            BitRate::Cbr(bitrate) => {
+                self.set_param(sys::AACENC_PARAM_AACENC_BITRATE, bitrate)?;
+                0
+            }
+            BitRate::VbrVeryLow => 1,
+            BitRate::VbrLow => 2,
+            BitRate::VbrMedium => 3,
+            BitRate::VbrHigh => 4,
+            BitRate::VbrVeryHigh => 5,
+        };
+
+        self.set_param(sys::AACENC_PARAM_AACENC_BITRATEMODE, bitrate_mode)
+    }
+
+    // (re)initializes whatever parts of the encoder changed parameters
+    // require, by calling encode with all null params according to docs
+    fn apply_params(&mut self) -> Result<(), EncoderError> {
+        check(unsafe { sys::aacEncEncode(self.ptr, ptr::null(), ptr::null(), ptr::null(), ptr::null_mut()) })
+    }
+
+    // applies a change to a running encoder, restoring the previous values
+    // of `params` if the library rejects it
+    fn reconfigure<F>(&mut self, params: &[sys::AACENC_PARAM], change: F) -> Result<(), EncoderError>
+        where F: FnOnce(&mut Self) -> Result<(), EncoderError>
+    {
+        let previous = params.iter()
+            .map(|&param| (param, self.get_param(param)))
+            .collect::<Vec<_>>();
+
+        let result = change(self).and_then(|()| self.apply_params());
+
+        if result.is_err() {
+            for &(param, value) in &previous {
+                let _ = self.set_param(param, value);
+            }
+
+            let _ = self.apply_params();
+        }
+
+        result
+    }
 }
 
 impl Drop for EncoderHandle {
@@ -74,7 +152,7 @@ impl Drop for EncoderHandle {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BitRate {
     Cbr(u32),
     VbrVeryLow,
@@ -86,8 +164,108 @@ pub enum BitRate {
 
 #[derive(Debug, Clone, Copy)]
 pub enum ChannelMode {
+    /// C
+    ///
+    /// Value: 1
     Mono,
+    /// L+R
+    ///
+    /// Value: 2
     Stereo,
+    /// C, L+R
+    ///
+    /// Value: 3
+    Mode1_2,
+    /// C, L+R, Rear
+    ///
+    /// Value: 4
+    Mode1_2_1,
+    /// C, L+R, LS+RS
+    ///
+    /// Value: 5
+    Mode1_2_2,
+    /// C, L+R, LS+RS, LFE (5.1)
+    ///
+    /// Value: 6
+    Mode1_2_2_1,
+    /// C, LC+RC, L+R, LS+RS, LFE (7.1, MPEG channel configuration 7)
+    ///
+    /// Value: 7
+    Mode1_2_2_2_1,
+    /// C, L+R, LS+RS, Crear, LFE
+    ///
+    /// Value: 11
+    Mode6_1,
+    /// C, L+R, LS+RS, Lrear+Rrear, LFE
+    ///
+    /// Value: 12
+    Mode7_1Back,
+    /// C, L+R, LS+RS, LFE, Ltop+Rtop
+    ///
+    /// Value: 14
+    Mode7_1TopFront,
+    /// C, L+R, LS+RS, Lrear+Rrear, LFE
+    ///
+    /// Value: 33
+    Mode7_1RearSurround,
+    /// C, LC+RC, L+R, LS+RS, LFE
+    ///
+    /// Value: 34
+    Mode7_1FrontCenter,
+    /// Stereo input coded as a mono downmix with parametric side
+    /// information. This configuration can be used only with
+    /// `Mpeg4EnhancedLowDelay` (ELDv2), `Encoder::new` fails with
+    /// `EncoderError::INVALID_CONFIG` otherwise.
+    ///
+    /// Value: 128
+    Mode212,
+}
+
+impl ChannelMode {
+    /// Number of interleaved input channels expected for this mode.
+    pub fn channels(&self) -> usize {
+        match self {
+            ChannelMode::Mono => 1,
+            ChannelMode::Stereo => 2,
+            ChannelMode::Mode1_2 => 3,
+            ChannelMode::Mode1_2_1 => 4,
+            ChannelMode::Mode1_2_2 => 5,
+            ChannelMode::Mode1_2_2_1 => 6,
+            ChannelMode::Mode1_2_2_2_1 => 8,
+            ChannelMode::Mode6_1 => 7,
+            ChannelMode::Mode7_1Back => 8,
+            ChannelMode::Mode7_1TopFront => 8,
+            ChannelMode::Mode7_1RearSurround => 8,
+            ChannelMode::Mode7_1FrontCenter => 8,
+            ChannelMode::Mode212 => 2,
+        }
+    }
+
+    fn validate(self, aot: AudioObjectType) -> Result<(), EncoderError> {
+        match (self, aot) {
+            (ChannelMode::Mode212, AudioObjectType::Mpeg4EnhancedLowDelay) => Ok(()),
+            (ChannelMode::Mode212, _) => Err(EncoderError::INVALID_CONFIG),
+            _ => Ok(()),
+        }
+    }
+
+    fn to_sys(self) -> sys::CHANNEL_MODE {
+        match self {
+            ChannelMode::Mono => sys::CHANNEL_MODE_MODE_1,
+            ChannelMode::Stereo => sys::CHANNEL_MODE_MODE_2,
+            ChannelMode::Mode1_2 => sys::CHANNEL_MODE_MODE_1_2,
+            ChannelMode::Mode1_2_1 => sys::CHANNEL_MODE_MODE_1_2_1,
+            ChannelMode::Mode1_2_2 => sys::CHANNEL_MODE_MODE_1_2_2,
+            ChannelMode::Mode1_2_2_1 => sys::CHANNEL_MODE_MODE_1_2_2_1,
+            ChannelMode::Mode1_2_2_2_1 => sys::CHANNEL_MODE_MODE_1_2_2_2_1,
+            ChannelMode::Mode6_1 => sys::CHANNEL_MODE_MODE_6_1,
+            ChannelMode::Mode7_1Back => sys::CHANNEL_MODE_MODE_7_1_BACK,
+            ChannelMode::Mode7_1TopFront => sys::CHANNEL_MODE_MODE_7_1_TOP_FRONT,
+            ChannelMode::Mode7_1RearSurround => sys::CHANNEL_MODE_MODE_7_1_REAR_SURROUND,
+            ChannelMode::Mode7_1FrontCenter => sys::CHANNEL_MODE_MODE_7_1_FRONT_CENTER,
+            ChannelMode::Mode212 => sys::CHANNEL_MODE_MODE_212,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -124,33 +302,536 @@ pub enum AudioObjectType {
     Mpeg2HeAac,
 }
 
+impl AudioObjectType {
+    fn has_implicit_sbr(self) -> bool {
+        matches!(self,
+            AudioObjectType::Mpeg4HeAac |
+            AudioObjectType::Mpeg4HeAacV2 |
+            AudioObjectType::Mpeg2HeAac)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SbrMode {
+    /// Use SBR as implied by the audio object type, ie. for the HE-AAC
+    /// object types only. `Mpeg4EnhancedLowDelay` is encoded without SBR,
+    /// as in earlier versions of this crate.
+    ///
+    /// Value: 0
+    Auto,
+    /// Disable Spectral Band Replication. Only valid for audio object types
+    /// which do not imply SBR.
+    ///
+    /// Value: 0
+    Disabled,
+    /// Enable Spectral Band Replication. Only valid for
+    /// `Mpeg4EnhancedLowDelay` and the HE-AAC object types.
+    ///
+    /// Value: 1
+    Enabled,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SbrRatio {
+    /// Use the default ratio for the audio object type: dual-rate SBR for
+    /// HE-AAC, downsampled SBR for ELD.
+    ///
+    /// Value: 0
+    Auto,
+    /// Downsampled SBR, core and SBR run at the same sampling rate. Only
+    /// valid for `Mpeg4EnhancedLowDelay`.
+    ///
+    /// Value: 1
+    Downsampled,
+    /// Dual-rate SBR, core runs at half the output sampling rate.
+    ///
+    /// Value: 2
+    DualRate,
+}
+
+fn validate_sbr(aot: AudioObjectType, mode: SbrMode, ratio: SbrRatio) -> Result<(), EncoderError> {
+    let eld = matches!(aot, AudioObjectType::Mpeg4EnhancedLowDelay);
+
+    let sbr_possible = match mode {
+        SbrMode::Auto => aot.has_implicit_sbr(),
+        SbrMode::Disabled if aot.has_implicit_sbr() => return Err(EncoderError::INVALID_CONFIG),
+        SbrMode::Disabled => false,
+        SbrMode::Enabled if !(aot.has_implicit_sbr() || eld) => return Err(EncoderError::INVALID_CONFIG),
+        SbrMode::Enabled => true,
+    };
+
+    match ratio {
+        SbrRatio::Auto => Ok(()),
+        SbrRatio::Downsampled if !(eld && sbr_possible) => Err(EncoderError::INVALID_CONFIG),
+        SbrRatio::DualRate if !sbr_possible => Err(EncoderError::INVALID_CONFIG),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MetadataMode {
+    /// Do not embed any metadata.
+    ///
+    /// Value: 0
+    None,
+    /// Embed MPEG dynamic_range_info metadata.
+    ///
+    /// Value: 1
+    DynamicRangeInfo,
+    /// Embed MPEG dynamic_range_info and ETSI ancillary data metadata.
+    ///
+    /// Value: 2
+    DynamicRangeInfoAndEtsi,
+    /// Embed ETSI ancillary data metadata.
+    ///
+    /// Value: 3
+    Etsi,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DrcProfile {
+    None,
+    FilmStandard,
+    FilmLight,
+    MusicStandard,
+    MusicLight,
+    Speech,
+    /// Do not signal the profile at all.
+    NotPresent,
+}
+
+impl DrcProfile {
+    fn to_sys(self) -> sys::AACENC_METADATA_DRC_PROFILE {
+        match self {
+            DrcProfile::None => sys::AACENC_METADATA_DRC_PROFILE_AACENC_METADATA_DRC_NONE,
+            DrcProfile::FilmStandard => sys::AACENC_METADATA_DRC_PROFILE_AACENC_METADATA_DRC_FILMSTANDARD,
+            DrcProfile::FilmLight => sys::AACENC_METADATA_DRC_PROFILE_AACENC_METADATA_DRC_FILMLIGHT,
+            DrcProfile::MusicStandard => sys::AACENC_METADATA_DRC_PROFILE_AACENC_METADATA_DRC_MUSICSTANDARD,
+            DrcProfile::MusicLight => sys::AACENC_METADATA_DRC_PROFILE_AACENC_METADATA_DRC_MUSICLIGHT,
+            DrcProfile::Speech => sys::AACENC_METADATA_DRC_PROFILE_AACENC_METADATA_DRC_SPEECH,
+            DrcProfile::NotPresent => sys::AACENC_METADATA_DRC_PROFILE_AACENC_METADATA_DRC_NOT_PRESENT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DrcPresentationMode {
+    NotIndicated,
+    Mode1,
+    Mode2,
+}
+
+/// Downmix level, as coded in ETSI TS 101 154 ancillary data.
+#[derive(Debug, Clone, Copy)]
+pub enum MixLevel {
+    /// Value: 0
+    Db0,
+    /// Value: 1
+    DbMinus1_5,
+    /// Value: 2
+    DbMinus3,
+    /// Value: 3
+    DbMinus4_5,
+    /// Value: 4
+    DbMinus6,
+    /// Value: 5
+    DbMinus7_5,
+    /// Value: 6
+    DbMinus9,
+    /// Value: 7
+    MinusInfinity,
+}
+
+impl MixLevel {
+    fn to_sys(self) -> u8 {
+        match self {
+            MixLevel::Db0 => 0,
+            MixLevel::DbMinus1_5 => 1,
+            MixLevel::DbMinus3 => 2,
+            MixLevel::DbMinus4_5 => 3,
+            MixLevel::DbMinus6 => 4,
+            MixLevel::DbMinus7_5 => 5,
+            MixLevel::DbMinus9 => 6,
+            MixLevel::MinusInfinity => 7,
+        }
+    }
+}
+
+/// Downmix metadata telling receivers with fewer output channels how to mix
+/// a multichannel stream down. Everything except `pce_mixdown_idx` is
+/// carried in ETSI ancillary data, which requires a `MetadataMode` including
+/// ETSI.
+#[derive(Debug, Clone, Copy)]
+pub struct DownmixMetadata {
+    /// Level at which the centre channel is mixed into the front channels.
+    pub center_mix_level: MixLevel,
+    /// Level at which the surround channels are mixed into the front
+    /// channels.
+    pub surround_mix_level: MixLevel,
+    /// Signal `center_mix_level` and `surround_mix_level` in the ETSI
+    /// ancillary data.
+    pub etsi_mix_levels: bool,
+    /// Signal the matrix mixdown index in the program config element.
+    pub pce_mixdown_idx: bool,
+    /// Extended downmix levels A and B (ext_downmixing_levels).
+    pub ext_downmix_levels: Option<(MixLevel, MixLevel)>,
+    /// Global gains in dB applied when downmixing to 5 and to 2 channels
+    /// respectively (ext_downmixing_global_gains), -15.75dB to 15.75dB in
+    /// steps of 0.25dB.
+    pub downmix_gains: Option<(f32, f32)>,
+    /// LFE downmix level index, 0 to 15 (ext_downmixing_lfe_level).
+    pub lfe_downmix_level: Option<u8>,
+}
+
+/// DRC, loudness and downmix metadata embedded by the encoder, see
+/// `Encoder::set_metadata`. Levels are in dB.
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    /// MPEG dynamic_range_info compression profile.
+    pub drc_profile: DrcProfile,
+    /// ETSI heavy compression profile.
+    pub comp_profile: DrcProfile,
+    /// Target reference level the DRC profile is computed for, -31.75dB to
+    /// 0dB.
+    pub drc_target_ref_level: f32,
+    /// Target reference level the heavy compression profile is computed for,
+    /// used to avoid overload, -31.75dB to 0dB.
+    pub comp_target_ref_level: f32,
+    /// Programme reference level (dialogue level), -31.75dB to 0dB in steps
+    /// of 0.25dB. `None` omits prog_ref_level from the bitstream.
+    pub prog_ref_level: Option<f32>,
+    pub drc_presentation_mode: DrcPresentationMode,
+    pub downmix: Option<DownmixMetadata>,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            drc_profile: DrcProfile::None,
+            comp_profile: DrcProfile::NotPresent,
+            drc_target_ref_level: -31.0,
+            comp_target_ref_level: -23.0,
+            prog_ref_level: None,
+            drc_presentation_mode: DrcPresentationMode::NotIndicated,
+            downmix: None,
+        }
+    }
+}
+
+// levels are passed to libfdk-aac as fixed point dB scaled by 2^16
+fn db_to_q16(db: f32) -> i32 {
+    (db * 65536.0) as i32
+}
+
+impl DownmixMetadata {
+    fn validate(&self) -> Result<(), EncoderError> {
+        let valid_gain = |gain: f32| gain.abs() <= 15.75 && (gain * 4.0).fract() == 0.0;
+
+        if let Some((gain_5, gain_2)) = self.downmix_gains {
+            if !valid_gain(gain_5) || !valid_gain(gain_2) {
+                return Err(EncoderError::INVALID_CONFIG);
+            }
+        }
+
+        if let Some(lfe_level) = self.lfe_downmix_level {
+            if lfe_level > 15 {
+                return Err(EncoderError::INVALID_CONFIG);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Metadata {
+    fn validate(&self) -> Result<(), EncoderError> {
+        let valid_level = |level: f32| (-31.75..=0.0).contains(&level);
+
+        if !valid_level(self.drc_target_ref_level) || !valid_level(self.comp_target_ref_level) {
+            return Err(EncoderError::INVALID_CONFIG);
+        }
+
+        if let Some(prog_ref_level) = self.prog_ref_level {
+            if !valid_level(prog_ref_level) || (prog_ref_level * 4.0).fract() != 0.0 {
+                return Err(EncoderError::INVALID_CONFIG);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_sys(self) -> Result<sys::AACENC_MetaData, EncoderError> {
+        self.validate()?;
+
+        if let Some(downmix) = &self.downmix {
+            downmix.validate()?;
+        }
+
+        let mut meta: sys::AACENC_MetaData = unsafe { mem::zeroed() };
+
+        meta.drc_profile = self.drc_profile.to_sys();
+        meta.comp_profile = self.comp_profile.to_sys();
+        meta.drc_TargetRefLevel = db_to_q16(self.drc_target_ref_level);
+        meta.comp_TargetRefLevel = db_to_q16(self.comp_target_ref_level);
+
+        if let Some(prog_ref_level) = self.prog_ref_level {
+            meta.prog_ref_level_present = 1;
+            meta.prog_ref_level = db_to_q16(prog_ref_level);
+        }
+
+        meta.drcPresentationMode = match self.drc_presentation_mode {
+            DrcPresentationMode::NotIndicated => 0,
+            DrcPresentationMode::Mode1 => 1,
+            DrcPresentationMode::Mode2 => 2,
+        };
+
+        if let Some(downmix) = self.downmix {
+            meta.centerMixLevel = downmix.center_mix_level.to_sys() as i8;
+            meta.surroundMixLevel = downmix.surround_mix_level.to_sys() as i8;
+            meta.ETSI_DmxLvl_present = downmix.etsi_mix_levels as u8;
+            meta.PCE_mixdown_idx_present = downmix.pce_mixdown_idx as u8;
+
+            let ext = &mut meta.ExtMetaData;
+
+            if let Some((level_a, level_b)) = downmix.ext_downmix_levels {
+                ext.extDownmixLevelEnable = 1;
+                ext.extDownmixLevel_A = level_a.to_sys();
+                ext.extDownmixLevel_B = level_b.to_sys();
+            }
+
+            if let Some((gain_5, gain_2)) = downmix.downmix_gains {
+                ext.dmxGainEnable = 1;
+                ext.dmxGain5 = db_to_q16(gain_5);
+                ext.dmxGain2 = db_to_q16(gain_2);
+            }
+
+            if let Some(lfe_level) = downmix.lfe_downmix_level {
+                ext.lfeDmxEnable = 1;
+                ext.lfeDmxLevel = lfe_level;
+            }
+
+            ext.extAncDataEnable = (ext.extDownmixLevelEnable | ext.dmxGainEnable | ext.lfeDmxEnable != 0) as u8;
+        }
+
+        Ok(meta)
+    }
+}
+
+fn validate_granule_length(aot: AudioObjectType, sample_rate: u32, sbr_mode: SbrMode, granule_length: u32) -> Result<(), EncoderError> {
+    let valid = match aot {
+        AudioObjectType::Mpeg4LowDelay => {
+            sample_rate <= 48000 && matches!(granule_length, 480 | 512)
+        }
+        AudioObjectType::Mpeg4EnhancedLowDelay => {
+            sample_rate <= 48000 && match granule_length {
+                480 | 512 => true,
+                // downscaled ELD, not available with SBR
+                120 | 128 | 240 | 256 => !matches!(sbr_mode, SbrMode::Enabled),
+                _ => false,
+            }
+        }
+        _ => granule_length == 1024,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(EncoderError::INVALID_CONFIG)
+    }
+}
+
 pub struct EncoderParams {
     pub bit_rate: BitRate,
     pub sample_rate: u32,
     pub transport: Transport,
     pub channels: ChannelMode,
     pub audio_object_type: AudioObjectType,
+    pub sbr_mode: SbrMode,
+    pub sbr_ratio: SbrRatio,
+    /// Enables the afterburner, which improves audio quality at the cost of
+    /// additional CPU time.
+    pub afterburner: bool,
+    /// Core encoder audio bandwidth in Hz, limited to 20kHz and not usable
+    /// with SBR. `None` lets the library determine the bandwidth.
+    pub bandwidth: Option<u32>,
+    /// Upper limit of the bitrate in bits/second, useful for capping VBR
+    /// peaks. `None` leaves the library default in place.
+    pub peak_bitrate: Option<u32>,
+    /// AudioMuxVersion (0, 1 or 2) used for LATM/LOAS transports. `None`
+    /// uses version 0.
+    pub audio_mux_version: Option<u32>,
+    /// Number of sub frames per transport frame for ADTS (up to 4) and
+    /// LATM/LOAS (up to 2). `None` uses one sub frame.
+    pub sub_frames: Option<u32>,
+    /// Frame count period for repeating the in-band StreamMuxConfig in
+    /// LATM/LOAS transports. `None` lets the library choose.
+    pub header_period: Option<u32>,
+    /// Enables CRC error protection, where each ADTS frame header carries
+    /// protection_absent = 0 and a CRC. libfdk-aac implements CRC protection
+    /// only for ADTS, `Encoder::new` fails with `EncoderError::INVALID_CONFIG`
+    /// for other transports.
+    pub protection: bool,
+    /// Bitrate in bits/second reserved for ancillary data passed to
+    /// `Encoder::encode_with_ancillary`. `None` disables ancillary data.
+    pub ancillary_bitrate: Option<u32>,
+    /// Which metadata to embed, see `Encoder::set_metadata`.
+    pub metadata_mode: MetadataMode,
+    /// Number of samples per channel in one core frame. `Mpeg4LowDelay`
+    /// supports 480 and 512 and `Mpeg4EnhancedLowDelay` additionally the
+    /// downscaled lengths 120, 128, 240 and 256 without SBR,
+    /// both at sample rates up to 48kHz. All other audio object types only
+    /// support 1024. `None` uses the default for the audio object type. The
+    /// resulting length is reported in `InfoStruct::frameLength`.
+    pub granule_length: Option<u32>,
+}
+
+impl Default for EncoderParams {
+    /// 128kbit/s AAC-LC stereo at 44.1kHz in ADTS, with every optional
+    /// setting left to the library.
+    fn default() -> Self {
+        EncoderParams {
+            bit_rate: BitRate::Cbr(128000),
+            sample_rate: 44100,
+            transport: Transport::Adts,
+            channels: ChannelMode::Stereo,
+            audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+            sbr_mode: SbrMode::Auto,
+            sbr_ratio: SbrRatio::Auto,
+            afterburner: false,
+            bandwidth: None,
+            peak_bitrate: None,
+            audio_mux_version: None,
+            sub_frames: None,
+            header_period: None,
+            protection: false,
+            ancillary_bitrate: None,
+            metadata_mode: MetadataMode::None,
+            granule_length: None,
+        }
+    }
 }
 
+/// An AAC encoder instance.
+///
+/// Encoding mutates the underlying libfdk-aac handle, so `encode` and `flush`
+/// require exclusive access. Queries such as `info` are available through a
+/// shared reference, but libfdk-aac may update internal state while
+/// answering them, so an `Encoder` can be moved to another thread but not
+/// shared between threads:
+///
+/// ```compile_fail
+/// use std::sync::Arc;
+/// use fdk_aac::enc::Encoder;
+///
+/// fn encode_shared(encoder: Arc<Encoder>, pcm: &[i16], out: &mut [u8]) {
+///     encoder.encode(pcm, out).unwrap();
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use fdk_aac::enc::Encoder;
+///
+/// fn flush_shared(encoder: &Encoder) {
+///     encoder.flush().unwrap();
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use std::sync::Arc;
+/// use std::thread;
+/// use fdk_aac::enc::Encoder;
+///
+/// fn info_shared(encoder: Arc<Encoder>) {
+///     let other = encoder.clone();
+///     thread::spawn(move || other.info().unwrap());
+///     encoder.info().unwrap();
+/// }
+/// ```
+///
+/// ```
+/// use std::thread;
+/// use fdk_aac::enc::{Encoder, EncoderParams};
+///
+/// fn encode_elsewhere(mut encoder: Encoder, pcm: Vec<i16>) {
+///     thread::spawn(move || {
+///         let mut output = vec![0u8; 8192];
+///         encoder.encode(&pcm, &mut output).unwrap();
+///     });
+/// }
+/// ```
 pub struct Encoder {
     handle: EncoderHandle,
+    channels: usize,
+    finished: bool,
+    metadata: Option<sys::AACENC_MetaData>,
 }
 
 #[derive(Debug)]
 pub enum Transport {
+    /// Audio Data Transport Stream.
+    ///
+    /// Value: 2
     Adts,
+    /// Raw access units.
+    ///
+    /// Value: 0
     Raw,
+    /// Audio Data Interchange Format, a single header at the start of the
+    /// stream.
+    ///
+    /// Value: 1
+    Adif,
+    /// LATM audio mux elements with in-band StreamMuxConfig
+    /// (muxConfigPresent = 1).
+    ///
+    /// Value: 6
+    LatmMcp1,
+    /// LATM audio mux elements with out-of-band StreamMuxConfig
+    /// (muxConfigPresent = 0).
+    ///
+    /// Value: 7
+    LatmMcp0,
+    /// LATM wrapped in the LOAS audio sync stream.
+    ///
+    /// Value: 10
+    Loas,
+}
+
+impl Transport {
+    fn to_sys(&self) -> sys::TRANSPORT_TYPE {
+        match self {
+            Transport::Adts => sys::TRANSPORT_TYPE_TT_MP4_ADTS,
+            Transport::Raw => sys::TRANSPORT_TYPE_TT_MP4_RAW,
+            Transport::Adif => sys::TRANSPORT_TYPE_TT_MP4_ADIF,
+            Transport::LatmMcp1 => sys::TRANSPORT_TYPE_TT_MP4_LATM_MCP1,
+            Transport::LatmMcp0 => sys::TRANSPORT_TYPE_TT_MP4_LATM_MCP0,
+            Transport::Loas => sys::TRANSPORT_TYPE_TT_MP4_LOAS,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct EncodeInfo {
     pub input_consumed: usize,
     pub output_size: usize,
+    pub ancillary_consumed: usize,
 }
 
 impl Encoder {
     pub fn new(params: EncoderParams) -> Result<Self, EncoderError> {
-        let handle = EncoderHandle::alloc(0, 2 /* hardcode stereo */)?;
+        params.channels.validate(params.audio_object_type)?;
+
+        if params.protection && !matches!(params.transport, Transport::Adts) {
+            return Err(EncoderError::INVALID_CONFIG);
+        }
+        validate_sbr(params.audio_object_type, params.sbr_mode, params.sbr_ratio)?;
+
+        if let Some(granule_length) = params.granule_length {
+            validate_granule_length(params.audio_object_type, params.sample_rate, params.sbr_mode, granule_length)?;
+        }
+
+        let channels = params.channels.channels();
+        let mut handle = EncoderHandle::alloc(0, channels)?;
 
         unsafe {
             let aot = match params.audio_object_type {
@@ -165,44 +846,72 @@ impl Encoder {
 
             check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_AOT, aot as u32))?;
 
-            let bitrate_mode = match params.bit_rate {
-                BitRate::Cbr(bitrate) => {
-                    check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_BITRATE, bitrate))?;
-                    0
-                }
-                BitRate::VbrVeryLow => 1,
-                BitRate::VbrLow => 2,
-                BitRate::VbrMedium => 3,
-                BitRate::VbrHigh => 4,
-                BitRate::VbrVeryHigh => 5,
-            };
-
-            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_BITRATEMODE, bitrate_mode))?;
+            handle.set_bit_rate(params.bit_rate)?;
 
             check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_SAMPLERATE, params.sample_rate))?;
 
-            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_TRANSMUX, match params.transport {
-                Transport::Adts => 2,
-                Transport::Raw => 0,
+            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_TRANSMUX, params.transport.to_sys() as u32))?;
+
+            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_PROTECTION, params.protection as u32))?;
+
+            if let Some(ancillary_bitrate) = params.ancillary_bitrate {
+                check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_ANCILLARY_BITRATE, ancillary_bitrate))?;
+            }
+
+            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_METADATA_MODE, match params.metadata_mode {
+                MetadataMode::None => 0,
+                MetadataMode::DynamicRangeInfo => 1,
+                MetadataMode::DynamicRangeInfoAndEtsi => 2,
+                MetadataMode::Etsi => 3,
             }))?;
 
-            // hardcode SBR off for now
-            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_SBR_MODE, 0))?;
+            if let Some(audio_mux_version) = params.audio_mux_version {
+                check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_AUDIOMUXVER, audio_mux_version))?;
+            }
+
+            if let Some(sub_frames) = params.sub_frames {
+                check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_TPSUBFRAMES, sub_frames))?;
+            }
+
+            if let Some(header_period) = params.header_period {
+                check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_HEADER_PERIOD, header_period))?;
+            }
+
+            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_SBR_MODE, match params.sbr_mode {
+                SbrMode::Auto | SbrMode::Disabled => 0,
+                SbrMode::Enabled => 1,
+            }))?;
+
+            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_SBR_RATIO, match params.sbr_ratio {
+                SbrRatio::Auto => 0,
+                SbrRatio::Downsampled => 1,
+                SbrRatio::DualRate => 2,
+            }))?;
+
+            if let Some(granule_length) = params.granule_length {
+                check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_GRANULE_LENGTH, granule_length))?;
+            }
+
+            check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_AFTERBURNER, params.afterburner as u32))?;
+
+            if let Some(bandwidth) = params.bandwidth {
+                check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_BANDWIDTH, bandwidth))?;
+            }
+
+            if let Some(peak_bitrate) = params.peak_bitrate {
+                check(sys::aacEncoder_SetParam(handle.ptr, sys::AACENC_PARAM_AACENC_PEAK_BITRATE, peak_bitrate))?;
+            }
 
             check(sys::aacEncoder_SetParam(
                 handle.ptr,
                 sys::AACENC_PARAM_AACENC_CHANNELMODE,
-                match params.channels {
-                    ChannelMode::Mono => 1,
-                    ChannelMode::Stereo => 2,
-                },
+                params.channels.to_sys() as u32,
             ))?;
 
-            // call encode once with all null params according to docs
-            check(sys::aacEncEncode(handle.ptr, ptr::null(), ptr::null(), ptr::null(), ptr::null_mut()))?;
+            handle.apply_params()?;
         }
 
-        Ok(Encoder { handle })
+        Ok(Encoder { handle, channels, finished: false, metadata: None })
     }
 
     pub fn info(&self) -> Result<InfoStruct, EncoderError> {
@@ -211,19 +920,203 @@ impl Encoder {
         Ok(unsafe { info.assume_init() })
     }
 
-    pub fn encode(&self, input: &[i16], output: &mut [u8]) -> Result<EncodeInfo, EncoderError> {
-        let input_len = cmp::min(i32::max_value() as usize, input.len()) as i32;
+    /// Returns the AudioSpecificConfig describing the encoded stream. LATM
+    /// and LOAS transports carry a StreamMuxConfig instead, which is only
+    /// available through `info()`.
+    pub fn audio_specific_config(&self) -> Result<AudioSpecificConfig, EncoderError> {
+        match self.get_param(sys::AACENC_PARAM_AACENC_TRANSMUX) as sys::TRANSPORT_TYPE {
+            sys::TRANSPORT_TYPE_TT_MP4_LATM_MCP1 |
+            sys::TRANSPORT_TYPE_TT_MP4_LATM_MCP0 |
+            sys::TRANSPORT_TYPE_TT_MP4_LOAS => return Err(EncoderError::UNSUPPORTED_PARAMETER),
+            _ => {}
+        }
+
+        let info = self.info()?;
+        let conf = &info.confBuf[..cmp::min(info.confSize as usize, info.confBuf.len())];
+        AudioSpecificConfig::parse(conf).ok_or(EncoderError::INVALID_CONFIG)
+    }
+
+    /// Bitrate or VBR mode in effect.
+    pub fn bit_rate(&self) -> BitRate {
+        match self.get_param(sys::AACENC_PARAM_AACENC_BITRATEMODE) {
+            1 => BitRate::VbrVeryLow,
+            2 => BitRate::VbrLow,
+            3 => BitRate::VbrMedium,
+            4 => BitRate::VbrHigh,
+            5 => BitRate::VbrVeryHigh,
+            _ => BitRate::Cbr(self.get_param(sys::AACENC_PARAM_AACENC_BITRATE)),
+        }
+    }
+
+    /// Whether the afterburner is in effect.
+    pub fn afterburner(&self) -> bool {
+        self.get_param(sys::AACENC_PARAM_AACENC_AFTERBURNER) != 0
+    }
+
+    /// Core encoder audio bandwidth in Hz in effect.
+    pub fn bandwidth(&self) -> u32 {
+        self.get_param(sys::AACENC_PARAM_AACENC_BANDWIDTH)
+    }
+
+    /// Peak bitrate in bits/second in effect.
+    pub fn peak_bitrate(&self) -> u32 {
+        self.get_param(sys::AACENC_PARAM_AACENC_PEAK_BITRATE)
+    }
+
+    fn get_param(&self, param: sys::AACENC_PARAM) -> u32 {
+        self.handle.get_param(param)
+    }
+
+    /// Changes the bitrate or bitrate mode of the running encoder. Only the
+    /// parts of the encoder affected by the change are re-initialized, so
+    /// the encoded stream stays continuous. If the library rejects the new
+    /// bitrate the error is returned and the previous bitrate stays in
+    /// effect. Fails with `EncoderError::ENCODE_EOF` after `flush`.
+    pub fn set_bitrate(&mut self, bit_rate: BitRate) -> Result<(), EncoderError> {
+        self.check_running()?;
+        self.handle.reconfigure(
+            &[sys::AACENC_PARAM_AACENC_BITRATE, sys::AACENC_PARAM_AACENC_BITRATEMODE],
+            |handle| handle.set_bit_rate(bit_rate))
+    }
+
+    /// Changes the peak bitrate of the running encoder, see `set_bitrate`.
+    pub fn set_peak_bitrate(&mut self, peak_bitrate: u32) -> Result<(), EncoderError> {
+        self.check_running()?;
+        self.handle.reconfigure(
+            &[sys::AACENC_PARAM_AACENC_PEAK_BITRATE],
+            |handle| handle.set_param(sys::AACENC_PARAM_AACENC_PEAK_BITRATE, peak_bitrate))
+    }
+
+    /// Enables or disables the afterburner of the running encoder, see
+    /// `set_bitrate`.
+    pub fn set_afterburner(&mut self, afterburner: bool) -> Result<(), EncoderError> {
+        self.check_running()?;
+        self.handle.reconfigure(
+            &[sys::AACENC_PARAM_AACENC_AFTERBURNER],
+            |handle| handle.set_param(sys::AACENC_PARAM_AACENC_AFTERBURNER, afterburner as u32))
+    }
+
+    // a finished encoder must not be re-initialized by a reconfiguration
+    fn check_running(&self) -> Result<(), EncoderError> {
+        if self.finished {
+            Err(EncoderError::ENCODE_EOF)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of interleaved input channels this encoder was configured with.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Encodes interleaved PCM. `input` must contain whole sample frames, ie.
+    /// its length must be a multiple of the configured channel count,
+    /// otherwise `EncoderError::INVALID_INPUT_LENGTH` is returned.
+    pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<EncodeInfo, EncoderError> {
+        self.encode_with_ancillary(input, &[], output)
+    }
+
+    /// Like `encode`, additionally embedding `ancillary` bytes into the
+    /// bitstream. Requires `EncoderParams::ancillary_bitrate` to be set. The
+    /// number of ancillary bytes the encoder took is reported in
+    /// `EncodeInfo::ancillary_consumed`.
+    pub fn encode_with_ancillary(&mut self, input: &[i16], ancillary: &[u8], output: &mut [u8]) -> Result<EncodeInfo, EncoderError> {
+        self.check_running()?;
+
+        // usize::is_multiple_of needs Rust 1.87
+        #[allow(clippy::manual_is_multiple_of)]
+        if input.len() % self.channels != 0 {
+            return Err(EncoderError::INVALID_INPUT_LENGTH);
+        }
+
+        let input_len = cmp::min(i32::MAX as usize, input.len()) as i32;
+
+        self.encode_buffers(input, input_len, ancillary, output)
+    }
+
+    /// Sets the metadata embedded from the next encoded frame on. The library
+    /// keeps applying it until it is replaced, so this can be called once up
+    /// front or before any frame which changes it. Has no effect unless
+    /// `EncoderParams::metadata_mode` enables metadata. Fails with
+    /// `EncoderError::INVALID_CONFIG` for out of range reference levels,
+    /// downmix gains or LFE downmix level.
+    pub fn set_metadata(&mut self, metadata: &Metadata) -> Result<(), EncoderError> {
+        self.metadata = Some(metadata.to_sys()?);
+        Ok(())
+    }
+
+    /// Drains the samples still held in the encoder's look-ahead delay at the
+    /// end of the stream and returns the remaining access units. The encoder
+    /// is finished afterwards, further calls to `encode` or `flush` return
+    /// `EncoderError::ENCODE_EOF`.
+    pub fn flush(&mut self) -> Result<Vec<Vec<u8>>, EncoderError> {
+        self.check_running()?;
+
+        let mut output = vec![0u8; self.info()?.maxOutBufBytes as usize];
+        let mut access_units = Vec::new();
+
+        loop {
+            match self.encode_buffers(&[], -1, &[], &mut output) {
+                Ok(info) => {
+                    if info.output_size > 0 {
+                        access_units.push(output[..info.output_size].to_vec());
+                    }
+                }
+                Err(EncoderError::ENCODE_EOF) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.finished = true;
+
+        Ok(access_units)
+    }
+
+    fn encode_buffers(&mut self, input: &[i16], input_len: i32, ancillary: &[u8], output: &mut [u8]) -> Result<EncodeInfo, EncoderError> {
+        let ancillary_len = cmp::min(i32::MAX as usize, ancillary.len()) as i32;
+
+        let mut metadata = self.metadata.take();
+
+        let mut num_input_bufs = 0;
+        let mut input_bufs: [*mut c_void; 3] = [ptr::null_mut(); 3];
+        let mut input_buf_idents: [c_int; 3] = [0; 3];
+        let mut input_buf_sizes: [c_int; 3] = [0; 3];
+        let mut input_buf_el_sizes: [c_int; 3] = [0; 3];
+
+        let mut add_input_buf = |buf: *mut c_void, ident: sys::AACENC_BufferIdentifier, size: c_int, el_size: usize| {
+            input_bufs[num_input_bufs] = buf;
+            input_buf_idents[num_input_bufs] = ident as c_int;
+            input_buf_sizes[num_input_bufs] = size;
+            input_buf_el_sizes[num_input_bufs] = el_size as c_int;
+            num_input_bufs += 1;
+        };
+
+        add_input_buf(input.as_ptr() as *mut c_void,
+            sys::AACENC_BufferIdentifier_IN_AUDIO_DATA,
+            cmp::min(i32::MAX as usize, input.len()) as c_int,
+            mem::size_of::<i16>());
+
+        if !ancillary.is_empty() {
+            add_input_buf(ancillary.as_ptr() as *mut c_void,
+                sys::AACENC_BufferIdentifier_IN_ANCILLRY_DATA,
+                ancillary_len,
+                mem::size_of::<u8>());
+        }
+
+        if let Some(metadata) = metadata.as_mut() {
+            add_input_buf(metadata as *mut _ as *mut c_void,
+                sys::AACENC_BufferIdentifier_IN_METADATA_SETUP,
+                mem::size_of::<sys::AACENC_MetaData>() as c_int,
+                mem::size_of::<sys::AACENC_MetaData>());
+        }
 
-        let mut input_buf = input.as_ptr() as *mut i16;
-        let mut input_buf_ident: c_int = sys::AACENC_BufferIdentifier_IN_AUDIO_DATA as c_int;
-        let mut input_buf_size: c_int = input_len as c_int;
-        let mut input_buf_el_size: c_int = mem::size_of::<i16>() as c_int;
         let input_desc = sys::AACENC_BufDesc {
-            numBufs: 1,
-            bufs: &mut input_buf as *mut _ as *mut *mut c_void,
-            bufferIdentifiers: &mut input_buf_ident as *mut c_int,
-            bufSizes: &mut input_buf_size as *mut c_int,
-            bufElSizes: &mut input_buf_el_size as *mut c_int,
+            numBufs: num_input_bufs as c_int,
+            bufs: input_bufs.as_mut_ptr(),
+            bufferIdentifiers: input_buf_idents.as_mut_ptr(),
+            bufSizes: input_buf_sizes.as_mut_ptr(),
+            bufElSizes: input_buf_el_sizes.as_mut_ptr(),
         };
 
         let mut output_buf = output.as_mut_ptr();
@@ -240,16 +1133,25 @@ impl Encoder {
 
         let in_args = sys::AACENC_InArgs {
             numInSamples: input_len,
-            numAncBytes: 0,
+            numAncBytes: ancillary_len,
         };
 
         let mut out_args = unsafe { mem::zeroed() };
 
-        check(unsafe { sys::aacEncEncode(self.handle.ptr, &input_desc, &output_desc, &in_args, &mut out_args) })?;
+        let result = check(unsafe { sys::aacEncEncode(self.handle.ptr, &input_desc, &output_desc, &in_args, &mut out_args) });
+
+        if result.is_err() {
+            // the library may not have taken the metadata, pass it again
+            // with the next frame
+            self.metadata = metadata;
+        }
+
+        result?;
 
         Ok(EncodeInfo {
             output_size: out_args.numOutBytes as usize,
             input_consumed: out_args.numInSamples as usize,
+            ancillary_consumed: out_args.numAncBytes as usize,
         })
     }
 }
@@ -259,3 +1161,102 @@ impl Debug for Encoder {
         write!(f, "Encoder {{ handle: {:?} }}", self.handle.ptr)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sbr_mode_must_match_audio_object_type() {
+        let lc = AudioObjectType::Mpeg4LowComplexity;
+        let he_aac = AudioObjectType::Mpeg4HeAac;
+        let eld = AudioObjectType::Mpeg4EnhancedLowDelay;
+
+        assert_eq!(validate_sbr(lc, SbrMode::Auto, SbrRatio::Auto), Ok(()));
+        assert_eq!(validate_sbr(lc, SbrMode::Disabled, SbrRatio::Auto), Ok(()));
+        assert_eq!(validate_sbr(lc, SbrMode::Enabled, SbrRatio::Auto), Err(EncoderError::INVALID_CONFIG));
+        assert_eq!(validate_sbr(AudioObjectType::Mpeg4LowDelay, SbrMode::Enabled, SbrRatio::Auto), Err(EncoderError::INVALID_CONFIG));
+        assert_eq!(validate_sbr(AudioObjectType::Mpeg2Aac, SbrMode::Enabled, SbrRatio::Auto), Err(EncoderError::INVALID_CONFIG));
+
+        for &aot in [he_aac, AudioObjectType::Mpeg4HeAacV2, AudioObjectType::Mpeg2HeAac].iter() {
+            assert_eq!(validate_sbr(aot, SbrMode::Auto, SbrRatio::Auto), Ok(()));
+            assert_eq!(validate_sbr(aot, SbrMode::Enabled, SbrRatio::DualRate), Ok(()));
+            assert_eq!(validate_sbr(aot, SbrMode::Disabled, SbrRatio::Auto), Err(EncoderError::INVALID_CONFIG));
+        }
+
+        assert_eq!(validate_sbr(eld, SbrMode::Auto, SbrRatio::Auto), Ok(()));
+        assert_eq!(validate_sbr(eld, SbrMode::Disabled, SbrRatio::Auto), Ok(()));
+        assert_eq!(validate_sbr(eld, SbrMode::Enabled, SbrRatio::Auto), Ok(()));
+    }
+
+    #[test]
+    fn metadata_levels_are_range_checked() {
+        assert!(Metadata::default().to_sys().is_ok());
+
+        let valid = Metadata {
+            drc_target_ref_level: -31.75,
+            comp_target_ref_level: 0.0,
+            prog_ref_level: Some(-23.25),
+            ..Default::default()
+        };
+
+        let meta = valid.to_sys().unwrap();
+        assert_eq!(meta.prog_ref_level_present, 1);
+        assert_eq!(meta.prog_ref_level, -23 * 65536 - 16384);
+
+        let invalid = [
+            Metadata { drc_target_ref_level: -32.0, ..Default::default() },
+            Metadata { drc_target_ref_level: 0.5, ..Default::default() },
+            Metadata { comp_target_ref_level: -40.0, ..Default::default() },
+            Metadata { comp_target_ref_level: f32::NAN, ..Default::default() },
+            Metadata { prog_ref_level: Some(1.0), ..Default::default() },
+            Metadata { prog_ref_level: Some(-32.0), ..Default::default() },
+            Metadata { prog_ref_level: Some(-20.1), ..Default::default() },
+        ];
+
+        for metadata in invalid.iter() {
+            assert_eq!(metadata.to_sys().err(), Some(EncoderError::INVALID_CONFIG), "{:?}", metadata);
+        }
+    }
+
+    #[test]
+    fn granule_length_depends_on_audio_object_type() {
+        let lc = AudioObjectType::Mpeg4LowComplexity;
+        let ld = AudioObjectType::Mpeg4LowDelay;
+        let eld = AudioObjectType::Mpeg4EnhancedLowDelay;
+
+        assert_eq!(validate_granule_length(lc, 48000, SbrMode::Auto, 1024), Ok(()));
+        assert_eq!(validate_granule_length(lc, 48000, SbrMode::Auto, 960), Err(EncoderError::INVALID_CONFIG));
+
+        for &length in [480, 512].iter() {
+            assert_eq!(validate_granule_length(ld, 48000, SbrMode::Auto, length), Ok(()));
+            assert_eq!(validate_granule_length(eld, 48000, SbrMode::Enabled, length), Ok(()));
+            assert_eq!(validate_granule_length(ld, 96000, SbrMode::Auto, length), Err(EncoderError::INVALID_CONFIG));
+        }
+
+        for &length in [120, 128, 240, 256].iter() {
+            assert_eq!(validate_granule_length(eld, 48000, SbrMode::Disabled, length), Ok(()));
+            assert_eq!(validate_granule_length(eld, 48000, SbrMode::Auto, length), Ok(()));
+            assert_eq!(validate_granule_length(eld, 48000, SbrMode::Enabled, length), Err(EncoderError::INVALID_CONFIG));
+            assert_eq!(validate_granule_length(ld, 48000, SbrMode::Auto, length), Err(EncoderError::INVALID_CONFIG));
+        }
+
+        assert_eq!(validate_granule_length(eld, 48000, SbrMode::Disabled, 1024), Err(EncoderError::INVALID_CONFIG));
+    }
+
+    #[test]
+    fn sbr_ratio_requires_sbr() {
+        let eld = AudioObjectType::Mpeg4EnhancedLowDelay;
+
+        assert_eq!(validate_sbr(eld, SbrMode::Enabled, SbrRatio::Downsampled), Ok(()));
+        assert_eq!(validate_sbr(eld, SbrMode::Enabled, SbrRatio::DualRate), Ok(()));
+        // Auto encodes ELD without SBR
+        assert_eq!(validate_sbr(eld, SbrMode::Auto, SbrRatio::Downsampled), Err(EncoderError::INVALID_CONFIG));
+        assert_eq!(validate_sbr(eld, SbrMode::Disabled, SbrRatio::DualRate), Err(EncoderError::INVALID_CONFIG));
+
+        // downsampled SBR only exists for ELD
+        assert_eq!(validate_sbr(AudioObjectType::Mpeg4HeAac, SbrMode::Auto, SbrRatio::Downsampled), Err(EncoderError::INVALID_CONFIG));
+        assert_eq!(validate_sbr(AudioObjectType::Mpeg4LowComplexity, SbrMode::Auto, SbrRatio::DualRate), Err(EncoderError::INVALID_CONFIG));
+        assert_eq!(validate_sbr(AudioObjectType::Mpeg4LowComplexity, SbrMode::Disabled, SbrRatio::Downsampled), Err(EncoderError::INVALID_CONFIG));
+    }
+}
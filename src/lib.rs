@@ -1,2 +1,3 @@
 pub mod enc;
 pub mod dec;
+pub mod asc;
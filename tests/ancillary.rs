@@ -0,0 +1,89 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, Transport};
+use fdk_aac::enc::{Encoder, EncoderParams};
+
+const FRAMES: usize = 16;
+const PAYLOAD_LEN: usize = 8;
+
+fn params() -> EncoderParams {
+    EncoderParams {
+        ancillary_bitrate: Some(4000),
+        ..Default::default()
+    }
+}
+
+// timecode like payload for input frame `frame`
+fn payload(frame: usize) -> Vec<u8> {
+    (0..PAYLOAD_LEN).map(|i| (frame * PAYLOAD_LEN + i) as u8).collect()
+}
+
+/// Encodes `FRAMES` frames with a payload queued per input frame, passing
+/// on whatever the encoder did not take yet. Returns the access units and
+/// the ancillary bytes in the order the encoder consumed them.
+fn encode_with_payloads(encoder: &mut Encoder) -> (Vec<Vec<u8>>, Vec<u8>) {
+    let pcm = common::sine(2, 1024, |_| true);
+    let mut output = vec![0u8; encoder.info().unwrap().maxOutBufBytes as usize];
+    let mut access_units = Vec::new();
+    let mut pending = Vec::new();
+    let mut consumed = Vec::new();
+
+    for frame in 0..FRAMES {
+        pending.extend(payload(frame));
+        let mut input = &pcm[..];
+
+        while !input.is_empty() {
+            let info = encoder.encode_with_ancillary(input, &pending, &mut output).unwrap();
+            assert!(info.ancillary_consumed <= pending.len());
+
+            consumed.extend(pending.drain(..info.ancillary_consumed));
+
+            if info.output_size > 0 {
+                access_units.push(output[..info.output_size].to_vec());
+            }
+
+            input = &input[info.input_consumed..];
+        }
+    }
+
+    access_units.extend(encoder.flush().unwrap());
+    (access_units, consumed)
+}
+
+#[test]
+fn encoder_consumes_ancillary_data() {
+    let mut encoder = Encoder::new(params()).unwrap();
+    assert!(encoder.info().unwrap().maxAncBytes as usize >= PAYLOAD_LEN);
+
+    let (access_units, consumed) = encode_with_payloads(&mut encoder);
+    assert!(!access_units.is_empty());
+
+    // the reserved bitrate carries a payload per frame, consumed in order
+    let expected = (0..FRAMES).flat_map(payload).collect::<Vec<_>>();
+    assert!(consumed.len() >= (FRAMES - 1) * PAYLOAD_LEN, "{} bytes consumed", consumed.len());
+    assert_eq!(consumed[..], expected[..consumed.len()]);
+}
+
+#[test]
+fn ancillary_data_round_trip() {
+    let mut encoder = Encoder::new(params()).unwrap();
+    let (access_units, consumed) = encode_with_payloads(&mut encoder);
+
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+    decoder.enable_ancillary_data(1024).unwrap();
+
+    let mut pcm = vec![0i16; 4096];
+    let mut received = Vec::new();
+
+    for access_unit in &access_units {
+        decoder.fill(access_unit).unwrap();
+        decoder.decode_frame(&mut pcm).unwrap();
+
+        for element in decoder.ancillary_data() {
+            received.extend_from_slice(element);
+        }
+    }
+
+    assert!(!consumed.is_empty());
+    assert_eq!(received, consumed);
+}
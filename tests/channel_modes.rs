@@ -0,0 +1,79 @@
+mod common;
+
+use fdk_aac::enc::{AudioObjectType, BitRate, ChannelMode, Encoder, EncoderError, EncoderParams, Transport};
+
+const MODES: [ChannelMode; 12] = [
+    ChannelMode::Mono,
+    ChannelMode::Stereo,
+    ChannelMode::Mode1_2,
+    ChannelMode::Mode1_2_1,
+    ChannelMode::Mode1_2_2,
+    ChannelMode::Mode1_2_2_1,
+    ChannelMode::Mode1_2_2_2_1,
+    ChannelMode::Mode6_1,
+    ChannelMode::Mode7_1Back,
+    ChannelMode::Mode7_1TopFront,
+    ChannelMode::Mode7_1RearSurround,
+    ChannelMode::Mode7_1FrontCenter,
+];
+
+fn params(channels: ChannelMode) -> EncoderParams {
+    EncoderParams {
+        bit_rate: BitRate::Cbr(64000 * channels.channels() as u32),
+        sample_rate: 48000,
+        channels,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn every_channel_mode_encodes() {
+    for &mode in MODES.iter() {
+        let channels = mode.channels();
+        let encoder = Encoder::new(params(mode)).unwrap_or_else(|e| panic!("{:?}: {}", mode, e));
+        assert_eq!(encoder.channels(), channels);
+        assert_eq!(encoder.info().unwrap().inputChannels as usize, channels, "{:?}", mode);
+
+        let access_units = common::encode(params(mode), &common::sine(channels, 1024, |_| true));
+        assert!(!access_units.is_empty(), "{:?}", mode);
+    }
+}
+
+fn mode_212(audio_object_type: AudioObjectType) -> EncoderParams {
+    EncoderParams {
+        bit_rate: BitRate::Cbr(48000),
+        sample_rate: 48000,
+        channels: ChannelMode::Mode212,
+        audio_object_type,
+        // ADTS cannot carry the low delay object types
+        transport: Transport::Loas,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn mode_212_requires_eld() {
+    let access_units = common::encode(mode_212(AudioObjectType::Mpeg4EnhancedLowDelay), &common::sine(2, 1024, |_| true));
+    assert!(!access_units.is_empty());
+
+    for &aot in [AudioObjectType::Mpeg4LowComplexity, AudioObjectType::Mpeg4HeAacV2, AudioObjectType::Mpeg4LowDelay].iter() {
+        assert_eq!(Encoder::new(mode_212(aot)).unwrap_err(), EncoderError::INVALID_CONFIG, "{:?}", aot);
+    }
+}
+
+#[test]
+fn partial_sample_frames_are_rejected() {
+    let mut output = vec![0u8; 8192];
+
+    for &mode in [ChannelMode::Stereo, ChannelMode::Mode1_2_2_1, ChannelMode::Mode6_1].iter() {
+        let channels = mode.channels();
+        let mut encoder = Encoder::new(params(mode)).unwrap();
+
+        let pcm = common::sine(channels, 1024, |_| true);
+        assert_eq!(encoder.encode(&pcm[..pcm.len() - 1], &mut output).unwrap_err(), EncoderError::INVALID_INPUT_LENGTH, "{:?}", mode);
+        assert_eq!(encoder.encode(&pcm[..channels + 1], &mut output).unwrap_err(), EncoderError::INVALID_INPUT_LENGTH, "{:?}", mode);
+
+        // the encoder is still usable afterwards
+        assert_eq!(encoder.encode(&pcm, &mut output).unwrap().input_consumed, pcm.len());
+    }
+}
@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+
+use fdk_aac::dec::{Decoder, DecoderError};
+use fdk_aac::enc::{Encoder, EncoderParams};
+
+/// Interleaved sine tone of `frames` samples per channel, present on the
+/// channels for which `active` returns true.
+pub fn sine(channels: usize, frames: usize, active: impl Fn(usize) -> bool) -> Vec<i16> {
+    (0..frames * channels)
+        .map(|i| {
+            if active(i % channels) {
+                (((i / channels) as f32 * 0.05).sin() * 8000.0) as i16
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// Encodes `pcm`, advancing through the input by what the encoder reports
+/// as consumed, and returns the non-empty access units including the
+/// flushed ones.
+pub fn encode(params: EncoderParams, pcm: &[i16]) -> Vec<Vec<u8>> {
+    let mut encoder = Encoder::new(params).unwrap();
+    encode_with(&mut encoder, pcm)
+}
+
+pub fn encode_with(encoder: &mut Encoder, pcm: &[i16]) -> Vec<Vec<u8>> {
+    let mut access_units = encode_unflushed(encoder, pcm);
+    access_units.extend(encoder.flush().unwrap());
+    access_units
+}
+
+/// Like `encode_with`, but leaves the encoder running, so the stream ends
+/// without the audio still held in the encoder delay.
+pub fn encode_unflushed(encoder: &mut Encoder, pcm: &[i16]) -> Vec<Vec<u8>> {
+    let mut output = vec![0u8; encoder.info().unwrap().maxOutBufBytes as usize];
+    let mut access_units = Vec::new();
+    let mut input = pcm;
+
+    while !input.is_empty() {
+        let info = encoder.encode(input, &mut output).unwrap();
+        assert!(info.input_consumed > 0 || info.output_size > 0, "encoder made no progress");
+
+        if info.output_size > 0 {
+            access_units.push(output[..info.output_size].to_vec());
+        }
+
+        input = &input[info.input_consumed..];
+    }
+
+    access_units
+}
+
+/// Feeds each access unit to `decoder` and decodes every frame it yields,
+/// returning the concatenated PCM output.
+pub fn decode(decoder: &mut Decoder, access_units: &[Vec<u8>]) -> Vec<i16> {
+    let mut pcm = vec![0i16; 8 * 4096];
+    let mut output = Vec::new();
+
+    for access_unit in access_units {
+        assert_eq!(decoder.fill(access_unit).unwrap(), access_unit.len());
+
+        loop {
+            match decoder.decode_frame(&mut pcm) {
+                Ok(()) => output.extend_from_slice(&pcm[..decoder.decoded_frame_size()]),
+                Err(DecoderError::NOT_ENOUGH_BITS) => break,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    output
+}
+
+pub fn energy(pcm: &[i16]) -> i64 {
+    pcm.iter().map(|&sample| (sample as i64).pow(2)).sum()
+}
+
+/// Reads a stream from `tests/fixtures`, see the README there for how the
+/// fixtures are produced.
+pub fn fixture(name: &str) -> Vec<u8> {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path.display(), e))
+}
+
+/// Splits a stream with a sync layer, eg. LOAS, into chunks to `fill` the
+/// decoder with.
+pub fn chunks(stream: &[u8]) -> Vec<Vec<u8>> {
+    stream.chunks(2048).map(|chunk| chunk.to_vec()).collect()
+}
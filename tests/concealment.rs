@@ -0,0 +1,79 @@
+mod common;
+
+use fdk_aac::dec::{ConcealMethod, Decoder, Transport};
+use fdk_aac::enc::{BitRate, ChannelMode, EncoderParams};
+
+const FRAME_SIZE: usize = 1024;
+const LOST: [usize; 3] = [8, 16, 17];
+
+fn access_units() -> Vec<Vec<u8>> {
+    let params = EncoderParams {
+        bit_rate: BitRate::Cbr(64000),
+        sample_rate: 48000,
+        channels: ChannelMode::Mono,
+        ..Default::default()
+    };
+
+    common::encode(params, &common::sine(1, 32 * FRAME_SIZE, |_| true))
+}
+
+/// Decodes `access_units` one frame per access unit, concealing the ones in
+/// `lost`, and returns the output of each call.
+fn decode_frames(method: ConcealMethod, access_units: &[Vec<u8>], lost: &[usize]) -> Vec<Vec<i16>> {
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+    decoder.set_conceal_method(method).unwrap();
+
+    let mut pcm = vec![0i16; 4096];
+
+    access_units.iter().enumerate()
+        .map(|(i, access_unit)| {
+            if lost.contains(&i) {
+                decoder.conceal_frame(&mut pcm).unwrap();
+            } else {
+                assert_eq!(decoder.fill(access_unit).unwrap(), access_unit.len());
+                decoder.decode_frame(&mut pcm).unwrap();
+            }
+
+            assert_eq!(decoder.decoded_frame_size(), FRAME_SIZE);
+            pcm[..FRAME_SIZE].to_vec()
+        })
+        .collect()
+}
+
+fn assert_gapless(method: ConcealMethod) {
+    let access_units = access_units();
+    let reference = decode_frames(method, &access_units, &[]);
+    let concealed = decode_frames(method, &access_units, &LOST);
+
+    assert_eq!(concealed.len(), reference.len());
+
+    // EnergyInterpolation delays the output by a frame, so check the frame
+    // after each loss as well
+    for &i in &LOST {
+        assert!(common::energy(&concealed[i]) > 0, "frame {} is silent", i);
+        assert!(common::energy(&concealed[i + 1]) > 0, "frame {} is silent", i + 1);
+    }
+
+    // once the overlap with the concealed frames has been flushed out, the
+    // output lines up with the lossless decode again; a dropped or
+    // duplicated frame would shift the tone against the reference
+    let last_loss = LOST[LOST.len() - 1];
+
+    for i in (last_loss + 4)..reference.len() {
+        let error = reference[i].iter().zip(&concealed[i])
+            .map(|(&a, &b)| (a as i64 - b as i64).pow(2))
+            .sum::<i64>();
+
+        assert!(error * 100 <= common::energy(&reference[i]), "frame {} does not match", i);
+    }
+}
+
+#[test]
+fn noise_substitution_is_gapless() {
+    assert_gapless(ConcealMethod::NoiseSubstitution);
+}
+
+#[test]
+fn energy_interpolation_is_gapless() {
+    assert_gapless(ConcealMethod::EnergyInterpolation);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, LimiterConfig, LimiterMode, Transport};
+use fdk_aac::enc::{Encoder, EncoderParams};
+
+const CHANNELS: usize = 2;
+const FRAME: usize = 1024;
+
+fn decoder() -> Decoder {
+    Decoder::new(Transport::Adts).unwrap()
+}
+
+#[test]
+fn flush_returns_the_delayed_tail() {
+    // ends in the middle of the tone
+    let mut encoder = Encoder::new(EncoderParams::default()).unwrap();
+    let pcm = common::sine(CHANNELS, 16 * FRAME, |_| true);
+    let access_units = common::encode_unflushed(&mut encoder, &pcm);
+    let end = access_units.len() - 1;
+
+    let mut flushed = decoder();
+    common::decode(&mut flushed, &access_units[..end]);
+
+    let mut pcm = vec![0i16; 8 * 4096];
+    flushed.flush(&mut pcm).unwrap();
+
+    let delay = flushed.stream_info().output_delay;
+    assert!(delay > 0);
+    assert!(flushed.decoded_frame_size() >= delay * CHANNELS);
+
+    let tail = &pcm[..delay * CHANNELS];
+    assert!(common::energy(tail) > 0);
+
+    // the tail is what the next frame would have started with
+    let mut continued = decoder();
+    let decoded = common::decode(&mut continued, &access_units[..end + 1]);
+    let next = &decoded[end * FRAME * CHANNELS..][..delay * CHANNELS];
+
+    let error = tail.iter().zip(next)
+        .map(|(&a, &b)| (a as i64 - b as i64).pow(2))
+        .sum::<i64>();
+
+    assert!(error * 100 < common::energy(next), "error {} energy {}", error, common::energy(next));
+}
+
+#[test]
+fn seek_reset_discards_previous_audio() {
+    // a tone followed by silence
+    let pcm = common::sine(CHANNELS, 32 * FRAME, |_| true).into_iter()
+        .enumerate()
+        .map(|(i, sample)| if i < 8 * FRAME * CHANNELS { sample } else { 0 })
+        .collect::<Vec<_>>();
+
+    let access_units = common::encode(EncoderParams::default(), &pcm);
+
+    // jump from within the tone to well into the silence
+    let (before, after) = (&access_units[..6], &access_units[20..]);
+
+    let decoder = || {
+        let mut decoder = decoder();
+        decoder.set_limiter(&LimiterConfig { mode: LimiterMode::Disabled, ..Default::default() }).unwrap();
+        decoder
+    };
+
+    let mut fresh = decoder();
+    let expected = common::decode(&mut fresh, after);
+
+    let mut reset = decoder();
+    common::decode(&mut reset, before);
+    reset.seek_reset().unwrap();
+    let decoded = common::decode(&mut reset, after);
+
+    let mut jumped = decoder();
+    common::decode(&mut jumped, before);
+    let bleeding = common::decode(&mut jumped, after);
+
+    assert_eq!(decoded, expected);
+
+    // without the reset the tone bleeds into the first frame after the jump
+    let first = ..FRAME * CHANNELS;
+    assert!(common::energy(&bleeding[first]) > 0);
+    assert!(common::energy(&decoded[first]) * 1000 < common::energy(&bleeding[first]));
+}
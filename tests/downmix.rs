@@ -0,0 +1,153 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, DecoderError, DownmixConfig, OutputChannels, Transport as DecTransport};
+use fdk_aac::enc::{ChannelMode, DownmixMetadata, Encoder, EncoderError, EncoderParams, Metadata, MetadataMode, MixLevel};
+
+// default MPEG input channel order of 5.1: C L R Ls Rs LFE
+const CENTER: usize = 0;
+const LEFT: usize = 1;
+const RIGHT: usize = 2;
+const LEFT_SURROUND: usize = 3;
+
+fn downmix_metadata(center_mix_level: MixLevel, surround_mix_level: MixLevel) -> DownmixMetadata {
+    DownmixMetadata {
+        center_mix_level,
+        surround_mix_level,
+        etsi_mix_levels: true,
+        pce_mixdown_idx: false,
+        ext_downmix_levels: None,
+        downmix_gains: None,
+        lfe_downmix_level: None,
+    }
+}
+
+// A 5.1 tone on `channel` alone, decoded to stereo.
+fn decode_to_stereo(channel: usize, downmix: DownmixMetadata) -> Vec<i16> {
+    let mut encoder = Encoder::new(EncoderParams {
+        bit_rate: fdk_aac::enc::BitRate::Cbr(320000),
+        sample_rate: 48000,
+        channels: ChannelMode::Mode1_2_2_1,
+        metadata_mode: MetadataMode::Etsi,
+        ..Default::default()
+    }).unwrap();
+
+    encoder.set_metadata(&Metadata {
+        downmix: Some(downmix),
+        ..Default::default()
+    }).unwrap();
+
+    let access_units = common::encode_with(&mut encoder, &common::sine(6, 32 * 1024, |c| c == channel));
+
+    let mut decoder = Decoder::new(DecTransport::Adts).unwrap();
+    decoder.set_downmix(&DownmixConfig {
+        max_output_channels: Some(OutputChannels::Count(2)),
+        ..Default::default()
+    }).unwrap();
+
+    let pcm = common::decode(&mut decoder, &access_units);
+    assert_eq!(decoder.stream_info().num_channels, 2);
+
+    pcm
+}
+
+fn stereo_energy(channel: usize, downmix: DownmixMetadata) -> i64 {
+    common::energy(&decode_to_stereo(channel, downmix))
+}
+
+fn channel_energy(pcm: &[i16], channel: usize) -> i64 {
+    common::energy(&pcm.iter().skip(channel).step_by(2).copied().collect::<Vec<_>>())
+}
+
+#[test]
+fn front_channels_keep_their_side() {
+    let center = decode_to_stereo(CENTER, downmix_metadata(MixLevel::DbMinus3, MixLevel::DbMinus3));
+    let (left, right) = (channel_energy(&center, 0), channel_energy(&center, 1));
+    assert!(left > 0 && (left - right).abs() < left / 100, "left {} right {}", left, right);
+
+    let left = decode_to_stereo(LEFT, downmix_metadata(MixLevel::DbMinus3, MixLevel::DbMinus3));
+    assert!(channel_energy(&left, 1) < channel_energy(&left, 0) / 1000);
+
+    let right = decode_to_stereo(RIGHT, downmix_metadata(MixLevel::DbMinus3, MixLevel::DbMinus3));
+    assert!(channel_energy(&right, 0) < channel_energy(&right, 1) / 1000);
+}
+
+#[test]
+fn center_mix_level_is_applied() {
+    let full = stereo_energy(CENTER, downmix_metadata(MixLevel::Db0, MixLevel::Db0));
+    let attenuated = stereo_energy(CENTER, downmix_metadata(MixLevel::DbMinus9, MixLevel::Db0));
+
+    // -9dB is an energy ratio of 0.126
+    let ratio = attenuated as f64 / full as f64;
+    assert!(ratio > 0.08 && ratio < 0.2, "ratio {}", ratio);
+}
+
+#[test]
+fn surround_mix_level_is_applied() {
+    let full = stereo_energy(LEFT_SURROUND, downmix_metadata(MixLevel::Db0, MixLevel::Db0));
+    let muted = stereo_energy(LEFT_SURROUND, downmix_metadata(MixLevel::Db0, MixLevel::MinusInfinity));
+
+    assert!(full > 0);
+    assert!(muted < full / 1000, "muted {} full {}", muted, full);
+}
+
+#[test]
+fn out_of_range_downmix_metadata_is_rejected() {
+    let mut encoder = Encoder::new(EncoderParams {
+        channels: ChannelMode::Mode1_2_2_1,
+        metadata_mode: MetadataMode::Etsi,
+        ..Default::default()
+    }).unwrap();
+
+    let invalid = [
+        DownmixMetadata { lfe_downmix_level: Some(16), ..downmix_metadata(MixLevel::Db0, MixLevel::Db0) },
+        DownmixMetadata { downmix_gains: Some((16.0, 0.0)), ..downmix_metadata(MixLevel::Db0, MixLevel::Db0) },
+        DownmixMetadata { downmix_gains: Some((0.0, -0.1)), ..downmix_metadata(MixLevel::Db0, MixLevel::Db0) },
+    ];
+
+    for downmix in invalid.iter() {
+        let metadata = Metadata { downmix: Some(*downmix), ..Default::default() };
+        assert_eq!(encoder.set_metadata(&metadata), Err(EncoderError::INVALID_CONFIG));
+    }
+
+    let valid = DownmixMetadata {
+        downmix_gains: Some((-15.75, 2.25)),
+        lfe_downmix_level: Some(15),
+        ..downmix_metadata(MixLevel::Db0, MixLevel::Db0)
+    };
+
+    assert_eq!(encoder.set_metadata(&Metadata { downmix: Some(valid), ..Default::default() }), Ok(()));
+}
+
+#[test]
+fn unsupported_output_channels_are_rejected() {
+    let mut decoder = Decoder::new(DecTransport::Adts).unwrap();
+
+    for &channels in [0, 3, 4, 5, 7, 9].iter() {
+        let config = DownmixConfig {
+            // would be applied first if the counts were not validated up front
+            channel_mapping: Some(fdk_aac::dec::ChannelMapping::Mpeg),
+            max_output_channels: Some(OutputChannels::Count(channels)),
+            ..Default::default()
+        };
+
+        assert_eq!(decoder.set_downmix(&config), Err(DecoderError::SET_PARAM_FAIL), "{} channels", channels);
+    }
+
+    for &channels in [1, 2, 6, 8].iter() {
+        let config = DownmixConfig {
+            min_output_channels: Some(OutputChannels::Count(channels)),
+            max_output_channels: Some(OutputChannels::Count(channels)),
+            ..Default::default()
+        };
+
+        assert_eq!(decoder.set_downmix(&config), Ok(()), "{} channels", channels);
+    }
+
+    let stream = DownmixConfig {
+        min_output_channels: Some(OutputChannels::Stream),
+        max_output_channels: Some(OutputChannels::Stream),
+        ..Default::default()
+    };
+
+    assert_eq!(decoder.set_downmix(&stream), Ok(()));
+}
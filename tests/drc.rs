@@ -0,0 +1,87 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, DecoderError, DrcConfig, DrcReferenceLevel, Transport};
+use fdk_aac::enc::{DrcPresentationMode, DrcProfile, Encoder, EncoderParams, Metadata, MetadataMode};
+
+#[test]
+fn signalled_drc_metadata_is_reported() {
+    let mut encoder = Encoder::new(EncoderParams {
+        metadata_mode: MetadataMode::DynamicRangeInfoAndEtsi,
+        ..Default::default()
+    }).unwrap();
+
+    encoder.set_metadata(&Metadata {
+        drc_profile: DrcProfile::FilmStandard,
+        comp_profile: DrcProfile::FilmStandard,
+        prog_ref_level: Some(-20.0),
+        drc_presentation_mode: DrcPresentationMode::Mode1,
+        ..Default::default()
+    }).unwrap();
+
+    let access_units = common::encode_with(&mut encoder, &common::sine(2, 16 * 1024, |_| true));
+
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+    decoder.set_drc(&DrcConfig {
+        reference_level: Some(DrcReferenceLevel::Level(-24.0)),
+        ..Default::default()
+    }).unwrap();
+
+    common::decode(&mut decoder, &access_units);
+
+    let info = decoder.stream_info();
+    assert_eq!(info.drc_prog_ref_level, Some(-20.0));
+    assert_eq!(info.drc_presentation_mode, Some(1));
+}
+
+#[test]
+fn reference_level_is_range_checked() {
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+
+    for &db in [-9.75, 0.0, -32.0].iter() {
+        let config = DrcConfig {
+            reference_level: Some(DrcReferenceLevel::Level(db)),
+            heavy_compression: Some(true),
+            ..Default::default()
+        };
+
+        assert_eq!(decoder.set_drc(&config), Err(DecoderError::SET_PARAM_FAIL), "{}", db);
+    }
+
+    for &level in [DrcReferenceLevel::Level(-10.0), DrcReferenceLevel::Level(-31.75), DrcReferenceLevel::Off].iter() {
+        let config = DrcConfig { reference_level: Some(level), ..Default::default() };
+        assert_eq!(decoder.set_drc(&config), Ok(()), "{:?}", level);
+    }
+}
+
+#[test]
+fn factors_and_target_level_are_range_checked() {
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+
+    let invalid = [
+        DrcConfig { boost_factor: Some(1.5), ..Default::default() },
+        DrcConfig { boost_factor: Some(f32::NAN), ..Default::default() },
+        DrcConfig { attenuation_factor: Some(-0.5), ..Default::default() },
+        DrcConfig { attenuation_factor: Some(f32::NAN), ..Default::default() },
+        DrcConfig { enc_target_level: Some(1.0), ..Default::default() },
+        DrcConfig { enc_target_level: Some(-40.0), ..Default::default() },
+        // valid settings are not applied alongside an invalid one
+        DrcConfig {
+            reference_level: Some(DrcReferenceLevel::Level(-24.0)),
+            boost_factor: Some(2.0),
+            ..Default::default()
+        },
+    ];
+
+    for config in invalid.iter() {
+        assert_eq!(decoder.set_drc(config), Err(DecoderError::SET_PARAM_FAIL), "{:?}", config);
+    }
+
+    let valid = DrcConfig {
+        boost_factor: Some(0.5),
+        attenuation_factor: Some(1.0),
+        enc_target_level: Some(-31.75),
+        ..Default::default()
+    };
+
+    assert_eq!(decoder.set_drc(&valid), Ok(()));
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, Transport};
+use fdk_aac::enc::{Encoder, EncoderError, EncoderParams};
+
+const CHANNELS: usize = 2;
+// deliberately not a whole number of frames
+const SAMPLES: usize = 10 * 1024 + 300;
+
+fn decode(access_units: &[Vec<u8>]) -> Vec<i16> {
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+    common::decode(&mut decoder, access_units)
+}
+
+#[test]
+fn flush_drains_the_tail() {
+    let pcm = common::sine(CHANNELS, SAMPLES, |_| true);
+
+    let mut encoder = Encoder::new(EncoderParams::default()).unwrap();
+    let delay = encoder.info().unwrap().nDelay as usize;
+    assert!(delay > 0);
+
+    let mut access_units = common::encode_unflushed(&mut encoder, &pcm);
+    let unflushed = decode(&access_units).len() / CHANNELS;
+    assert!(unflushed < SAMPLES + delay, "{} samples without flushing", unflushed);
+
+    let flushed = encoder.flush().unwrap();
+    assert!(!flushed.is_empty());
+    access_units.extend(flushed);
+
+    let decoded = decode(&access_units);
+    assert!(decoded.len() / CHANNELS >= SAMPLES + delay, "{} samples, expected at least {}", decoded.len() / CHANNELS, SAMPLES + delay);
+
+    // the last input samples made it into the stream
+    let tail = &decoded[(SAMPLES + delay - 256) * CHANNELS..(SAMPLES + delay) * CHANNELS];
+    assert!(common::energy(tail) > 0);
+}
+
+#[test]
+fn encoder_is_finished_after_flush() {
+    let mut encoder = Encoder::new(EncoderParams::default()).unwrap();
+    let mut output = vec![0u8; encoder.info().unwrap().maxOutBufBytes as usize];
+
+    common::encode_unflushed(&mut encoder, &common::sine(CHANNELS, 2048, |_| true));
+    encoder.flush().unwrap();
+
+    let pcm = common::sine(CHANNELS, 1024, |_| true);
+    assert_eq!(encoder.encode(&pcm, &mut output).unwrap_err(), EncoderError::ENCODE_EOF);
+    assert_eq!(encoder.flush().unwrap_err(), EncoderError::ENCODE_EOF);
+}
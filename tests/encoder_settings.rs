@@ -0,0 +1,34 @@
+use fdk_aac::enc::{BitRate, Encoder, EncoderParams};
+
+#[test]
+fn settings_are_read_back() {
+    let encoder = Encoder::new(EncoderParams {
+        bit_rate: BitRate::Cbr(128000),
+        afterburner: true,
+        bandwidth: Some(15000),
+        peak_bitrate: Some(160000),
+        ..Default::default()
+    }).unwrap();
+
+    assert!(encoder.afterburner());
+    assert_eq!(encoder.bandwidth(), 15000);
+    assert_eq!(encoder.peak_bitrate(), 160000);
+}
+
+#[test]
+fn library_chosen_bandwidth_is_read_back() {
+    let encoder = Encoder::new(EncoderParams::default()).unwrap();
+
+    assert!(!encoder.afterburner());
+
+    // determined by the library from bitrate, sample rate and channels
+    let bandwidth = encoder.bandwidth();
+    assert!(bandwidth > 10000 && bandwidth <= 20000, "bandwidth {}", bandwidth);
+
+    let narrow = Encoder::new(EncoderParams {
+        bit_rate: BitRate::Cbr(32000),
+        ..Default::default()
+    }).unwrap();
+
+    assert!(narrow.bandwidth() < bandwidth, "{} at 32kbit/s, {} at 128kbit/s", narrow.bandwidth(), bandwidth);
+}
@@ -0,0 +1,45 @@
+use fdk_aac::enc::{AudioObjectType, BitRate, Encoder, EncoderError, EncoderParams, SbrMode, Transport};
+
+fn params(audio_object_type: AudioObjectType, granule_length: Option<u32>, sbr_mode: SbrMode) -> EncoderParams {
+    EncoderParams {
+        bit_rate: BitRate::Cbr(64000),
+        sample_rate: 48000,
+        // ADTS cannot carry the low delay object types
+        transport: Transport::Loas,
+        audio_object_type,
+        sbr_mode,
+        granule_length,
+        ..Default::default()
+    }
+}
+
+fn frame_length(params: EncoderParams) -> u32 {
+    Encoder::new(params).unwrap().info().unwrap().frameLength
+}
+
+#[test]
+fn frame_length_follows_granule_length() {
+    let ld = AudioObjectType::Mpeg4LowDelay;
+    let eld = AudioObjectType::Mpeg4EnhancedLowDelay;
+
+    assert_eq!(frame_length(params(ld, None, SbrMode::Auto)), 512);
+    assert_eq!(frame_length(params(ld, Some(480), SbrMode::Auto)), 480);
+    assert_eq!(frame_length(params(eld, Some(480), SbrMode::Disabled)), 480);
+    assert_eq!(frame_length(params(eld, Some(256), SbrMode::Disabled)), 256);
+    assert_eq!(frame_length(params(eld, Some(128), SbrMode::Disabled)), 128);
+}
+
+#[test]
+fn invalid_granule_length_is_rejected() {
+    let invalid = vec![
+        params(AudioObjectType::Mpeg4LowComplexity, Some(480), SbrMode::Auto),
+        params(AudioObjectType::Mpeg4LowDelay, Some(256), SbrMode::Auto),
+        params(AudioObjectType::Mpeg4EnhancedLowDelay, Some(256), SbrMode::Enabled),
+        params(AudioObjectType::Mpeg4EnhancedLowDelay, Some(1024), SbrMode::Disabled),
+        EncoderParams { sample_rate: 96000, ..params(AudioObjectType::Mpeg4EnhancedLowDelay, Some(480), SbrMode::Disabled) },
+    ];
+
+    for params in invalid {
+        assert_eq!(Encoder::new(params).unwrap_err(), EncoderError::INVALID_CONFIG);
+    }
+}
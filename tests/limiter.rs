@@ -0,0 +1,49 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, DecoderError, LimiterConfig, LimiterMode, Transport};
+use fdk_aac::enc::EncoderParams;
+
+fn decode_with(config: &LimiterConfig) -> Decoder {
+    let access_units = common::encode(EncoderParams::default(), &common::sine(2, 8 * 1024, |_| true));
+
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+    decoder.set_limiter(config).unwrap();
+    common::decode(&mut decoder, &access_units);
+
+    decoder
+}
+
+#[test]
+fn limiter_delay_is_part_of_output_delay() {
+    let enabled = decode_with(&LimiterConfig { mode: LimiterMode::Enabled, attack_time: 10, ..Default::default() });
+    let disabled = decode_with(&LimiterConfig { mode: LimiterMode::Disabled, ..Default::default() });
+
+    let enabled = enabled.stream_info();
+    let disabled = disabled.stream_info();
+
+    // 10ms at 44.1kHz
+    assert_eq!(enabled.limiter_delay, 441);
+    assert_eq!(disabled.limiter_delay, 0);
+    assert_eq!(enabled.output_delay - disabled.output_delay, enabled.limiter_delay);
+}
+
+#[test]
+fn invalid_limiter_config_is_rejected() {
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+
+    let invalid = [
+        LimiterConfig { attack_time: 0, ..Default::default() },
+        LimiterConfig { attack_time: 16, ..Default::default() },
+        LimiterConfig { release_time: 0, ..Default::default() },
+    ];
+
+    for config in invalid.iter() {
+        let config = LimiterConfig { mode: LimiterMode::Disabled, ..*config };
+        assert_eq!(decoder.set_limiter(&config), Err(DecoderError::SET_PARAM_FAIL));
+    }
+
+    // the rejected configs did not disable the limiter
+    let access_units = common::encode(EncoderParams::default(), &common::sine(2, 8 * 1024, |_| true));
+    common::decode(&mut decoder, &access_units);
+    assert_eq!(decoder.stream_info().limiter_delay, 15 * 44100 / 1000);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, DrcConfig, DrcEffect, DrcReferenceLevel, Transport};
+
+const FIXTURE: &str = "xhe_aac_loudness.loas";
+
+fn decode_energy(config: &DrcConfig) -> i64 {
+    let stream = common::fixture(FIXTURE);
+
+    let mut decoder = Decoder::new(Transport::Loas).unwrap();
+    decoder.set_drc(&DrcConfig {
+        reference_level: Some(DrcReferenceLevel::Level(-24.0)),
+        ..*config
+    }).unwrap();
+
+    let pcm = common::decode(&mut decoder, &common::chunks(&stream));
+
+    let info = decoder.stream_info();
+    assert!(info.is_usac());
+    assert!(info.mpegd_drc_present());
+
+    common::energy(&pcm)
+}
+
+// ratio of the energies in dB
+fn level_difference(a: i64, b: i64) -> f64 {
+    10.0 * (a as f64 / b as f64).log10()
+}
+
+#[test]
+#[ignore = "requires the xHE-AAC fixture, see tests/fixtures/README.md"]
+fn late_night_effect_changes_level() {
+    let none = decode_energy(&DrcConfig { effect: Some(DrcEffect::None), ..Default::default() });
+    let late_night = decode_energy(&DrcConfig { effect: Some(DrcEffect::LateNight), ..Default::default() });
+
+    let difference = level_difference(none, late_night);
+    assert!(difference.abs() > 1.0, "late night changed the level by {}dB", difference);
+}
+
+#[test]
+#[ignore = "requires the xHE-AAC fixture, see tests/fixtures/README.md"]
+fn album_mode_changes_level() {
+    let track = decode_energy(&DrcConfig { album_mode: Some(false), ..Default::default() });
+    let album = decode_energy(&DrcConfig { album_mode: Some(true), ..Default::default() });
+
+    // the fixture's album and track loudness differ by at least 3dB
+    let difference = level_difference(track, album);
+    assert!(difference.abs() > 2.0, "album mode changed the level by {}dB", difference);
+}
+
+#[test]
+fn every_effect_and_album_mode_is_accepted() {
+    let mut decoder = Decoder::new(Transport::Loas).unwrap();
+
+    let effects = [
+        DrcEffect::Off,
+        DrcEffect::None,
+        DrcEffect::LateNight,
+        DrcEffect::NoisyEnvironment,
+        DrcEffect::LimitedPlaybackRange,
+        DrcEffect::LowPlaybackLevel,
+        DrcEffect::DialogEnhancement,
+        DrcEffect::GeneralCompression,
+    ];
+
+    for &effect in effects.iter() {
+        let config = DrcConfig { effect: Some(effect), ..Default::default() };
+        assert_eq!(decoder.set_drc(&config), Ok(()), "{:?}", effect);
+    }
+
+    for &album_mode in [true, false].iter() {
+        let config = DrcConfig { album_mode: Some(album_mode), ..Default::default() };
+        assert_eq!(decoder.set_drc(&config), Ok(()), "album mode {}", album_mode);
+    }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, DecoderError, Transport as DecTransport};
+use fdk_aac::enc::{EncoderError, EncoderParams, Transport as EncTransport, Encoder};
+
+// ADTS header with protection_absent = 0: 7 header bytes and a 2 byte CRC
+const PROTECTED_HEADER_LEN: usize = 9;
+
+#[test]
+fn flipped_byte_is_a_crc_error() {
+    let params = EncoderParams {
+        protection: true,
+        ..Default::default()
+    };
+
+    let mut access_units = common::encode(params, &common::sine(2, 16 * 1024, |_| true));
+    assert!(access_units.len() > 8);
+
+    for access_unit in &access_units {
+        // protection_absent
+        assert_eq!(access_unit[1] & 1, 0);
+    }
+
+    let corrupt = access_units.len() / 2;
+    access_units[corrupt][PROTECTED_HEADER_LEN + 2] ^= 0xff;
+
+    let mut decoder = Decoder::new(DecTransport::Adts).unwrap();
+    let mut pcm = vec![0i16; 4096];
+
+    for (i, access_unit) in access_units.iter().enumerate() {
+        decoder.fill(access_unit).unwrap();
+
+        let result = decoder.decode_frame(&mut pcm);
+
+        if i == corrupt {
+            assert_eq!(result, Err(DecoderError::CRC_ERROR));
+        } else {
+            assert_eq!(result, Ok(()));
+        }
+    }
+}
+
+#[test]
+fn protection_requires_adts() {
+    for transport in [EncTransport::Raw, EncTransport::Adif, EncTransport::LatmMcp1, EncTransport::Loas] {
+        let params = EncoderParams {
+            transport,
+            protection: true,
+            ..Default::default()
+        };
+
+        assert_eq!(Encoder::new(params).err(), Some(EncoderError::INVALID_CONFIG));
+    }
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, Transport};
+use fdk_aac::enc::{AudioObjectType, BitRate, Encoder, EncoderError, EncoderParams, Transport as EncTransport};
+
+const CHANNELS: usize = 2;
+const FRAME: usize = 1024;
+
+// encodes `frames` frames of a tone, following `input_consumed`
+fn encode_frames(encoder: &mut Encoder, frames: usize, access_units: &mut Vec<Vec<u8>>) {
+    let pcm = common::sine(CHANNELS, frames * FRAME, |_| true);
+    let mut output = vec![0u8; encoder.info().unwrap().maxOutBufBytes as usize];
+    let mut input = &pcm[..];
+
+    while !input.is_empty() {
+        let info = encoder.encode(input, &mut output).unwrap();
+
+        if info.output_size > 0 {
+            access_units.push(output[..info.output_size].to_vec());
+        }
+
+        input = &input[info.input_consumed..];
+    }
+}
+
+#[test]
+fn bitrate_changes_keep_the_stream_continuous() {
+    let mut encoder = Encoder::new(EncoderParams::default()).unwrap();
+    let delay = encoder.info().unwrap().nDelay as usize;
+    let mut access_units = Vec::new();
+
+    encode_frames(&mut encoder, 8, &mut access_units);
+
+    encoder.set_bitrate(BitRate::Cbr(64000)).unwrap();
+    assert_eq!(encoder.bit_rate(), BitRate::Cbr(64000));
+    encode_frames(&mut encoder, 8, &mut access_units);
+
+    encoder.set_bitrate(BitRate::VbrMedium).unwrap();
+    assert_eq!(encoder.bit_rate(), BitRate::VbrMedium);
+    encode_frames(&mut encoder, 8, &mut access_units);
+
+    access_units.extend(encoder.flush().unwrap());
+
+    let mut decoder = Decoder::new(Transport::Adts).unwrap();
+    let pcm = common::decode(&mut decoder, &access_units);
+
+    // no input was dropped at the changes
+    let samples = 24 * FRAME;
+    assert!(pcm.len() / CHANNELS >= samples + delay, "{} samples", pcm.len() / CHANNELS);
+
+    // and every frame carrying the tone is audible
+    let first = delay / FRAME + 2;
+    let last = (samples + delay) / FRAME;
+
+    for (i, frame) in pcm.chunks(FRAME * CHANNELS).enumerate().take(last).skip(first) {
+        assert!(common::energy(frame) > 0, "frame {} is silent", i);
+    }
+}
+
+#[test]
+fn rejected_bitrate_keeps_the_previous_one() {
+    let mut encoder = Encoder::new(EncoderParams {
+        bit_rate: BitRate::Cbr(64000),
+        sample_rate: 48000,
+        audio_object_type: AudioObjectType::Mpeg4EnhancedLowDelay,
+        // ADTS cannot carry the low delay object types
+        transport: EncTransport::Loas,
+        ..Default::default()
+    }).unwrap();
+
+    let mut access_units = Vec::new();
+    encode_frames(&mut encoder, 4, &mut access_units);
+
+    // the low delay object types only support CBR
+    assert!(encoder.set_bitrate(BitRate::VbrHigh).is_err());
+    assert_eq!(encoder.bit_rate(), BitRate::Cbr(64000));
+
+    encode_frames(&mut encoder, 4, &mut access_units);
+    access_units.extend(encoder.flush().unwrap());
+
+    let mut decoder = Decoder::new(Transport::Loas).unwrap();
+    let pcm = common::decode(&mut decoder, &access_units);
+    assert_eq!(pcm.len(), access_units.len() * 512 * CHANNELS);
+}
+
+#[test]
+fn finished_encoder_cannot_be_reconfigured() {
+    let mut encoder = Encoder::new(EncoderParams::default()).unwrap();
+    encoder.flush().unwrap();
+
+    assert_eq!(encoder.set_bitrate(BitRate::Cbr(64000)), Err(EncoderError::ENCODE_EOF));
+    assert_eq!(encoder.set_peak_bitrate(160000), Err(EncoderError::ENCODE_EOF));
+    assert_eq!(encoder.set_afterburner(true), Err(EncoderError::ENCODE_EOF));
+    assert_eq!(encoder.bit_rate(), BitRate::Cbr(128000));
+}
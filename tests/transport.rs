@@ -0,0 +1,68 @@
+mod common;
+
+use fdk_aac::dec::{Decoder, Transport as DecTransport};
+use fdk_aac::enc::{Encoder, EncoderParams, Transport as EncTransport};
+
+const FRAMES: usize = 16;
+
+fn params(transport: EncTransport) -> EncoderParams {
+    EncoderParams {
+        transport,
+        afterburner: true,
+        ..Default::default()
+    }
+}
+
+fn assert_round_trip(decoder: &mut Decoder, access_units: &[Vec<u8>]) {
+    let pcm = common::decode(decoder, access_units);
+
+    assert_eq!(decoder.stream_info().num_channels, 2);
+    assert_eq!(decoder.stream_info().sample_rate, 44100);
+    assert_eq!(pcm.len(), access_units.len() * 1024 * 2);
+    assert!(common::energy(&pcm) > 0);
+}
+
+fn round_trip(enc_transport: EncTransport, dec_transport: DecTransport) {
+    let access_units = common::encode(params(enc_transport), &common::sine(2, FRAMES * 1024, |_| true));
+    assert!(access_units.len() >= FRAMES);
+
+    let mut decoder = Decoder::new(dec_transport).unwrap();
+    assert_round_trip(&mut decoder, &access_units);
+}
+
+#[test]
+fn adts_round_trip() {
+    round_trip(EncTransport::Adts, DecTransport::Adts);
+}
+
+#[test]
+fn adif_round_trip() {
+    round_trip(EncTransport::Adif, DecTransport::Adif);
+}
+
+#[test]
+fn latm_mcp1_round_trip() {
+    round_trip(EncTransport::LatmMcp1, DecTransport::LatmMcp1);
+}
+
+#[test]
+fn loas_round_trip() {
+    round_trip(EncTransport::Loas, DecTransport::Loas);
+}
+
+#[test]
+fn latm_mcp0_round_trip() {
+    let mut encoder = Encoder::new(params(EncTransport::LatmMcp0)).unwrap();
+
+    // for LATM the encoder's config buffer holds the StreamMuxConfig
+    let info = encoder.info().unwrap();
+    let stream_mux_config = info.confBuf[..info.confSize as usize].to_vec();
+    assert!(!stream_mux_config.is_empty());
+
+    let access_units = common::encode_with(&mut encoder, &common::sine(2, FRAMES * 1024, |_| true));
+    assert!(access_units.len() >= FRAMES);
+
+    let mut decoder = Decoder::new(DecTransport::LatmMcp0).unwrap();
+    decoder.config_raw(&stream_mux_config).unwrap();
+    assert_round_trip(&mut decoder, &access_units);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::convert::TryInto;
+
+use fdk_aac::asc::AudioSpecificConfig;
+use fdk_aac::dec::{AudioObjectType, Decoder, DecoderError, Transport};
+
+// Splits a fixture of access units each preceded by a 32-bit big-endian
+// length.
+fn access_units(name: &str) -> Vec<Vec<u8>> {
+    let stream = common::fixture(name);
+    let mut rest = &stream[..];
+    let mut access_units = Vec::new();
+
+    while !rest.is_empty() {
+        let (length, tail) = rest.split_at(4);
+        let length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+        let (access_unit, tail) = tail.split_at(length);
+
+        access_units.push(access_unit.to_vec());
+        rest = tail;
+    }
+
+    access_units
+}
+
+#[test]
+fn config_raw_with_usac_config() {
+    let config = common::fixture("xhe_aac.asc");
+
+    let asc = AudioSpecificConfig::parse(&config).unwrap();
+    assert_eq!(asc.audio_object_type(), 42);
+    assert_eq!(asc.channel_configuration(), 1);
+    assert_eq!(asc.frame_length(), Some(1024));
+    assert_eq!(asc.sampling_frequency(), 48000);
+    assert_eq!(asc.extension_sampling_frequency(), None);
+
+    let mut decoder = Decoder::new(Transport::Raw).unwrap();
+    decoder.config_raw(asc.as_bytes()).unwrap();
+
+    let access_units = access_units("xhe_aac.aus");
+    let pcm = common::decode(&mut decoder, &access_units);
+
+    let info = decoder.stream_info();
+    assert!(info.is_usac());
+    assert!(!info.sbr_present());
+    assert!(!info.mps_present());
+    assert_eq!(info.aot, AudioObjectType::Usac);
+    assert_eq!(info.aac_samples_per_frame, 1024);
+    assert_eq!(info.sample_rate, 48000);
+    assert_eq!(info.num_channels, 1);
+
+    // the fixture codes silence
+    assert_eq!(pcm.len(), access_units.len() * 1024);
+    assert_eq!(common::energy(&pcm), 0);
+}
+
+#[test]
+fn config_change_needs_restart() {
+    let mut decoder = Decoder::new(Transport::Raw).unwrap();
+    decoder.config_raw(&common::fixture("xhe_aac.asc")).unwrap();
+    common::decode(&mut decoder, &access_units("xhe_aac.aus"));
+    assert_eq!(decoder.stream_info().sample_rate, 48000);
+
+    let config = common::fixture("xhe_aac_44100.asc");
+    assert_eq!(decoder.config_raw(&config), Err(DecoderError::NEED_TO_RESTART));
+
+    decoder.restart().unwrap();
+    decoder.config_raw(&config).unwrap();
+
+    let access_units = access_units("xhe_aac_44100.aus");
+    let pcm = common::decode(&mut decoder, &access_units);
+
+    let info = decoder.stream_info();
+    assert!(info.is_usac());
+    assert_eq!(info.sample_rate, 44100);
+    assert_eq!(info.aac_samples_per_frame, 1024);
+    assert_eq!(pcm.len(), access_units.len() * 1024);
+}